@@ -0,0 +1,282 @@
+use super::*;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const WIN_SCORE: i32 = 10_000;
+
+#[derive(Copy, Clone, Debug)]
+pub struct SearchLimits {
+    pub max_depth: u8,
+    pub time_limit: Option<Duration>,
+    // Bounds the work without a clock, so a wasm search can't stall the page
+    pub node_limit: Option<u64>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits {
+            max_depth: 3,
+            time_limit: Some(Duration::from_secs(2)),
+            node_limit: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub score: i32,
+    pub depth: u8,
+    pub line: Vec<Move>,
+    pub nodes: u64,
+}
+
+#[derive(Clone)]
+pub struct AlphaBeta<Rb: Rulebook = StandardQuoridor> {
+    pub limits: SearchLimits,
+    pub evaluator: Arc<dyn Evaluator<Rb>>,
+    deadline: Option<Instant>,
+    nodes: u64,
+    aborted: bool,
+}
+
+impl<Rb: Rulebook<Move = Move>> AlphaBeta<Rb> {
+    pub fn new(limits: SearchLimits) -> Self {
+        Self::with_evaluator(limits, standard_evaluator())
+    }
+
+    pub fn with_evaluator<E: Evaluator<Rb> + 'static>(limits: SearchLimits, evaluator: E) -> Self {
+        AlphaBeta {
+            limits,
+            evaluator: Arc::new(evaluator),
+            deadline: None,
+            nodes: 0,
+            aborted: false,
+        }
+    }
+
+    pub fn search(&mut self, game: &QGame<Rb>) -> Option<SearchResult> {
+        self.search_with(game, |_| {})
+    }
+
+    // Reports each completed iteration, e.g. for engine info lines
+    pub fn search_with<F: FnMut(&SearchResult)>(
+        &mut self,
+        game: &QGame<Rb>,
+        mut on_depth: F,
+    ) -> Option<SearchResult> {
+        // Instant is unavailable on wasm, so it is only touched when there is a time limit
+        self.deadline = self.limits.time_limit.map(|limit| Instant::now() + limit);
+        self.nodes = 0;
+        self.aborted = false;
+
+        let mut best: Option<SearchResult> = None;
+        for depth in 1..=self.limits.max_depth {
+            let hint = best.as_ref().map(|r| r.line.clone()).unwrap_or_default();
+            let (score, line) = self.negamax(game, depth, 0, -WIN_SCORE - 1, WIN_SCORE + 1, &hint);
+            if self.aborted || line.is_empty() {
+                break;
+            }
+            best = Some(SearchResult {
+                best_move: line[0],
+                score,
+                depth,
+                line,
+                nodes: self.nodes,
+            });
+            on_depth(best.as_ref().unwrap());
+            if score.abs() > WIN_SCORE - 100 {
+                break;
+            }
+        }
+        best
+    }
+
+    // Scores every root move with a full window, for callers that want more than the best move
+    pub fn root_scores(&mut self, game: &QGame<Rb>, depth: u8) -> Vec<(Move, i32, Vec<Move>)> {
+        self.deadline = self.limits.time_limit.map(|limit| Instant::now() + limit);
+        self.nodes = 0;
+        self.aborted = false;
+
+        let mut scored = vec![];
+        for qmove in ordered_moves(game, None) {
+            let (score, line) = self.root_move(game, qmove, depth);
+            if self.aborted {
+                break;
+            }
+            scored.push((qmove, score, line));
+        }
+        scored.sort_by_key(|(_, score, _)| -score);
+        scored
+    }
+
+    // Scores a single root move to the given depth with no deadline, so it always finishes
+    pub fn score_move(&mut self, game: &QGame<Rb>, qmove: Move, depth: u8) -> (i32, Vec<Move>) {
+        self.deadline = None;
+        self.nodes = 0;
+        self.aborted = false;
+        self.root_move(game, qmove, depth)
+    }
+
+    fn root_move(&mut self, game: &QGame<Rb>, qmove: Move, depth: u8) -> (i32, Vec<Move>) {
+        let mut child = Clone::clone(game);
+        let (score, line) = match Rb::apply_move(&mut child, qmove) {
+            MoveResult::Win(id) if id == game.turn_of => (WIN_SCORE, vec![]),
+            MoveResult::Win(_) => (-WIN_SCORE, vec![]),
+            _ => {
+                let (score, line) = self.negamax(
+                    &child,
+                    depth.saturating_sub(1),
+                    1,
+                    -WIN_SCORE - 1,
+                    WIN_SCORE + 1,
+                    &[],
+                );
+                (-score, line)
+            }
+        };
+        (score, std::iter::once(qmove).chain(line).collect())
+    }
+
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    fn out_of_time(&mut self) -> bool {
+        self.nodes += 1;
+        if let Some(limit) = self.limits.node_limit {
+            self.aborted |= self.nodes > limit;
+        }
+        if self.nodes % 256 == 0 {
+            if let Some(deadline) = self.deadline {
+                self.aborted |= Instant::now() > deadline;
+            }
+        }
+        self.aborted
+    }
+
+    fn negamax(
+        &mut self,
+        game: &QGame<Rb>,
+        depth: u8,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        hint: &[Move],
+    ) -> (i32, Vec<Move>) {
+        if depth == 0 || self.out_of_time() {
+            return (self.evaluator.evaluate(game, game.turn_of), vec![]);
+        }
+
+        let moves = ordered_moves(game, hint.first().copied());
+        if moves.is_empty() {
+            return (self.evaluator.evaluate(game, game.turn_of), vec![]);
+        }
+
+        let mut best_score = -WIN_SCORE - 1;
+        let mut best_line = vec![];
+        for qmove in moves {
+            let mut child = Clone::clone(game);
+            let (score, line) = match Rb::apply_move(&mut child, qmove) {
+                MoveResult::Win(id) if id == game.turn_of => (WIN_SCORE - ply, vec![]),
+                MoveResult::Win(_) => (-WIN_SCORE + ply, vec![]),
+                _ => {
+                    let child_hint = match hint.split_first() {
+                        Some((&first, rest)) if first == qmove => rest,
+                        _ => &[][..],
+                    };
+                    let (score, line) =
+                        self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, child_hint);
+                    (-score, line)
+                }
+            };
+
+            if self.aborted {
+                break;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_line = std::iter::once(qmove).chain(line).collect();
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        (best_score, best_line)
+    }
+}
+
+pub fn ordered_moves<Rb: Rulebook<Move = Move>>(game: &QGame<Rb>, first: Option<Move>) -> Vec<Move> {
+    let player = game.turn_of;
+    let opponent = game
+        .pawn_positions
+        .iter()
+        .find(|(&id, _)| pawn_owner::<Rb>(id) != player)
+        .map_or(Position::from((4, 4)), |(_, &pos)| pos);
+
+    let mut scored = legal_moves(game)
+        .into_iter()
+        .map(|qmove| {
+            let key = match qmove {
+                _ if Some(qmove) == first => i32::MIN,
+                // Pawn moves that shorten our own path come first
+                Move::MovePawn(start, target) => {
+                    let mut child = Clone::clone(game);
+                    let id = child.pawn_positions.remove_by_right(&start).unwrap().0;
+                    child.pawn_positions.insert(id, target);
+                    -1000 + player_distance(&child, player).unwrap_or(u8::MAX) as i32
+                }
+                // Walls close to the opponent's pawn are the most likely to matter
+                Move::PlaceWall(wall) => {
+                    (wall.position.x as i32 - opponent.x as i32).abs()
+                        + (wall.position.y as i32 - opponent.y as i32).abs()
+                }
+                _ => i32::MAX,
+            };
+            (key, qmove)
+        })
+        .collect::<Vec<_>>();
+
+    scored.sort_by_key(|(key, _)| *key);
+    scored.into_iter().map(|(_, qmove)| qmove).collect()
+}
+
+impl<Rb: Rulebook<Move = Move>> Default for AlphaBeta<Rb> {
+    fn default() -> Self {
+        Self::new(SearchLimits::default())
+    }
+}
+
+impl<Rb: Rulebook<Move = Move>> Bot<Rb> for AlphaBeta<Rb> {
+    fn choose_move(&mut self, game: &QGame<Rb>) -> Option<Move> {
+        self.search(game).map(|result| result.best_move)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rulebooks::Quoridor5;
+
+    #[test]
+    fn finds_the_winning_pawn_move() {
+        let mut game = Quoridor5::initial_server();
+        game.pawn_positions.clear();
+        game.pawn_positions.insert(0, (2, 3).into());
+        game.pawn_positions.insert(1, (0, 2).into());
+
+        let limits = SearchLimits {
+            max_depth: 3,
+            time_limit: None,
+            node_limit: None,
+        };
+        let result = AlphaBeta::<Quoridor5>::new(limits).search(&game).unwrap();
+        assert_eq!(
+            result.best_move,
+            Move::MovePawn((2, 3).into(), (2, 4).into())
+        );
+        assert!(result.score > WIN_SCORE - 100);
+    }
+}
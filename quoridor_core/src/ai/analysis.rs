@@ -0,0 +1,175 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MoveAnalysis {
+    pub qmove: Move,
+    pub score: i32,
+    pub depth: u8,
+    pub line: Vec<Move>,
+}
+
+pub fn analyse<Rb: Rulebook<Move = Move>>(
+    game: &QGame<Rb>,
+    top_n: usize,
+    limits: SearchLimits,
+) -> Vec<MoveAnalysis> {
+    analyse_with(&mut AlphaBeta::new(limits), game, top_n)
+}
+
+pub fn analyse_with<Rb: Rulebook<Move = Move>>(
+    engine: &mut AlphaBeta<Rb>,
+    game: &QGame<Rb>,
+    top_n: usize,
+) -> Vec<MoveAnalysis> {
+    let mut best = vec![];
+    for depth in 1..=engine.limits.max_depth {
+        let scored = engine.root_scores(game, depth);
+        // A search cut short by the deadline has only seen some of the moves
+        if engine.aborted() && !best.is_empty() {
+            break;
+        }
+        best = scored
+            .into_iter()
+            .take(top_n)
+            .map(|(qmove, score, line)| MoveAnalysis {
+                qmove,
+                score,
+                depth,
+                line,
+            })
+            .collect();
+        if engine.aborted() {
+            break;
+        }
+    }
+    best
+}
+
+pub fn suggest_move<Rb: Rulebook<Move = Move>>(
+    game: &QGame<Rb>,
+    limits: SearchLimits,
+) -> Option<MoveAnalysis> {
+    analyse(game, 1, limits).into_iter().next()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Judgement {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+// Score losses are in evaluator units, where one step of path length is worth 10
+pub const INACCURACY_LOSS: i32 = 10;
+pub const MISTAKE_LOSS: i32 = 25;
+pub const BLUNDER_LOSS: i32 = 60;
+
+pub fn judge(best_score: i32, played_score: i32) -> Judgement {
+    let decided = WIN_SCORE - 100;
+    let loss = best_score - played_score;
+    if loss <= 0 {
+        Judgement::Best
+    } else if (best_score > decided && played_score <= decided)
+        || (played_score < -decided && best_score >= -decided)
+        || loss >= BLUNDER_LOSS
+    {
+        Judgement::Blunder
+    } else if loss >= MISTAKE_LOSS {
+        Judgement::Mistake
+    } else if loss >= INACCURACY_LOSS {
+        Judgement::Inaccuracy
+    } else {
+        Judgement::Good
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MoveReview {
+    pub ply: usize,
+    pub player: PlayerID,
+    pub played: Move,
+    pub played_score: i32,
+    pub best: MoveAnalysis,
+    pub judgement: Judgement,
+}
+
+// Scores every move of a game against the best alternative, both seen from the player who moved
+pub fn review_game<Rb: Rulebook<Move = Move>>(
+    positions: &[QGame<Rb>],
+    moves: &[Move],
+    limits: SearchLimits,
+) -> Vec<MoveReview> {
+    let mut engine = AlphaBeta::<Rb>::new(limits);
+    positions
+        .iter()
+        .zip(moves)
+        .enumerate()
+        .filter_map(|(ply, (game, &played))| {
+            let scored = analyse_with(&mut engine, game, usize::MAX);
+            let best = scored.first()?.clone();
+            // A search cut short may not have reached the played move, so it is scored on its own
+            // at the depth the others were
+            let played_score = match scored.iter().find(|m| m.qmove == played) {
+                Some(m) => m.score,
+                None => engine.score_move(game, played, best.depth).0,
+            };
+            Some(MoveReview {
+                ply,
+                player: game.turn_of,
+                played,
+                played_score,
+                judgement: judge(best.score, played_score),
+                best,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rulebooks::Quoridor5;
+
+    #[test]
+    fn single_moves_score_as_in_the_full_search() {
+        let mut game = Quoridor5::initial_server();
+        Quoridor5::apply_move(&mut game, Move::MovePawn((2, 0).into(), (2, 1).into()));
+        Quoridor5::apply_move(&mut game, Move::PlaceWall(Wall::horizontal((2, 3).into())));
+
+        let limits = SearchLimits {
+            max_depth: 2,
+            time_limit: None,
+            node_limit: None,
+        };
+        let mut engine = AlphaBeta::<Quoridor5>::new(limits);
+        for (qmove, score, _) in engine.root_scores(&game, 2) {
+            assert_eq!(AlphaBeta::new(limits).score_move(&game, qmove, 2).0, score);
+        }
+    }
+
+    #[test]
+    fn backward_steps_are_not_best() {
+        let mut game = Quoridor5::initial_server();
+        let forward = Move::MovePawn((2, 0).into(), (2, 1).into());
+        let reply = Move::MovePawn((2, 4).into(), (2, 3).into());
+        let back = Move::MovePawn((2, 1).into(), (2, 0).into());
+        let mut positions = vec![game.clone()];
+        for &qmove in [forward, reply].iter() {
+            Quoridor5::apply_move(&mut game, qmove);
+            positions.push(game.clone());
+        }
+
+        let limits = SearchLimits {
+            max_depth: 2,
+            time_limit: None,
+            node_limit: None,
+        };
+        let reviews = review_game(&positions, &[forward, reply, back], limits);
+        assert_eq!(reviews.len(), 3);
+        assert!(reviews[2].played_score < reviews[2].best.score);
+        assert_ne!(reviews[2].judgement, Judgement::Best);
+    }
+}
@@ -0,0 +1,233 @@
+use super::*;
+use crate::rng::SplitMix64;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+// Reflects the board left to right; wall x coordinates run one past the last column
+pub fn mirror_position<Rb: Rulebook>(pos: Position) -> Position {
+    Position::from((Rb::BOARD_SIZE - 1 - pos.x, pos.y))
+}
+
+pub fn mirror_wall<Rb: Rulebook>(wall: Wall) -> Wall {
+    // On a torus the seam column is both 0 and BOARD_SIZE
+    let x = if Rb::TOROIDAL {
+        (Rb::BOARD_SIZE - wall.position.x) % Rb::BOARD_SIZE
+    } else {
+        Rb::BOARD_SIZE - wall.position.x
+    };
+    Wall {
+        position: Position::from((x, wall.position.y)),
+        ..wall
+    }
+}
+
+pub fn mirror_move<Rb: Rulebook>(qmove: Move) -> Move {
+    match qmove {
+        Move::PlaceWall(wall) => Move::PlaceWall(mirror_wall::<Rb>(wall)),
+        Move::RemoveWall(wall) => Move::RemoveWall(mirror_wall::<Rb>(wall)),
+        Move::MoveWall(from, to) => {
+            Move::MoveWall(mirror_wall::<Rb>(from), mirror_wall::<Rb>(to))
+        }
+        Move::MovePawn(start, target) => {
+            Move::MovePawn(mirror_position::<Rb>(start), mirror_position::<Rb>(target))
+        }
+    }
+}
+
+pub fn mirror_game<Rb: Rulebook>(game: &QGame<Rb>) -> QGame<Rb> {
+    QGame {
+        pawn_positions: game
+            .pawn_positions
+            .iter()
+            .map(|(&id, &pos)| (id, mirror_position::<Rb>(pos)))
+            .collect(),
+        walls: game.walls.iter().map(|&w| mirror_wall::<Rb>(w)).collect(),
+        wall_owners: game
+            .wall_owners
+            .iter()
+            .map(|(&w, &owner)| (mirror_wall::<Rb>(w), owner))
+            .collect(),
+        ..Clone::clone(game)
+    }
+}
+
+fn position_hash<Rb: Rulebook>(game: &QGame<Rb>, mirrored: bool) -> u64 {
+    let pos = |p: Position| if mirrored { mirror_position::<Rb>(p) } else { p };
+
+    let mut pawns = game
+        .pawn_positions
+        .iter()
+        .map(|(&id, &p)| [id, pos(p).x, pos(p).y])
+        .collect::<Vec<_>>();
+    pawns.sort();
+
+    let mut walls = game
+        .walls
+        .iter()
+        .map(|&w| if mirrored { mirror_wall::<Rb>(w) } else { w })
+        .map(|w| [w.orientation as u8, w.position.x, w.position.y])
+        .collect::<Vec<_>>();
+    walls.sort();
+
+    let mut hash = fnv(FNV_OFFSET, &[game.turn_of]);
+    hash = fnv(hash, &game.wall_counts);
+    for pawn in pawns {
+        hash = fnv(hash, &pawn);
+    }
+    for wall in walls {
+        hash = fnv(hash, &wall);
+    }
+    hash
+}
+
+// The key is shared by a position and its mirror image; the flag says whether moves must be mirrored
+pub fn canonical_key<Rb: Rulebook>(game: &QGame<Rb>) -> (u64, bool) {
+    let plain = position_hash(game, false);
+    let mirrored = position_hash(game, true);
+    if mirrored < plain {
+        (mirrored, true)
+    } else {
+        (plain, false)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct BookMove {
+    pub qmove: Move,
+    pub weight: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OpeningBook {
+    pub entries: HashMap<u64, Vec<BookMove>>,
+}
+
+impl OpeningBook {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add<Rb: Rulebook>(&mut self, game: &QGame<Rb>, qmove: Move, weight: u32) {
+        let (key, mirrored) = canonical_key(game);
+        let qmove = if mirrored { mirror_move::<Rb>(qmove) } else { qmove };
+        let moves = self.entries.entry(key).or_insert_with(Vec::new);
+        match moves.iter_mut().find(|m| m.qmove == qmove) {
+            Some(existing) => existing.weight += weight,
+            None => moves.push(BookMove { qmove, weight }),
+        }
+    }
+
+    pub fn lookup<Rb: Rulebook>(&self, game: &QGame<Rb>) -> Vec<BookMove> {
+        let (key, mirrored) = canonical_key(game);
+        self.entries
+            .get(&key)
+            .map(|moves| {
+                moves
+                    .iter()
+                    .map(|m| BookMove {
+                        qmove: if mirrored { mirror_move::<Rb>(m.qmove) } else { m.qmove },
+                        weight: m.weight,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn prune(&mut self, min_weight: u32) {
+        for moves in self.entries.values_mut() {
+            moves.retain(|m| m.weight >= min_weight);
+        }
+        self.entries.retain(|_, moves| !moves.is_empty());
+    }
+
+    // Picks a legal book move with probability proportional to its weight
+    pub fn pick<Rb: Rulebook<Move = Move>>(
+        &self,
+        game: &QGame<Rb>,
+        rng: &mut SplitMix64,
+    ) -> Option<Move> {
+        let moves = self
+            .lookup(game)
+            .into_iter()
+            .filter(|m| m.weight > 0 && Rb::validate_move(game, m.qmove).is_ok())
+            .collect::<Vec<_>>();
+        let total = moves.iter().map(|m| m.weight as u64).sum::<u64>();
+        if total == 0 {
+            return None;
+        }
+
+        let mut roll = rng.below(total);
+        for m in moves {
+            if roll < m.weight as u64 {
+                return Some(m.qmove);
+            }
+            roll -= m.weight as u64;
+        }
+        None
+    }
+}
+
+pub struct BookBot<B> {
+    pub book: Arc<OpeningBook>,
+    pub inner: B,
+    rng: SplitMix64,
+}
+
+impl<B> BookBot<B> {
+    pub fn new(book: Arc<OpeningBook>, inner: B, seed: u64) -> Self {
+        BookBot {
+            book,
+            inner,
+            rng: SplitMix64::new(seed),
+        }
+    }
+}
+
+impl<Rb: Rulebook<Move = Move>, B: Bot<Rb>> Bot<Rb> for BookBot<B> {
+    fn choose_move(&mut self, game: &QGame<Rb>) -> Option<Move> {
+        self.book
+            .pick(game, &mut self.rng)
+            .or_else(|| self.inner.choose_move(game))
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.inner.failure()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rulebooks::Quoridor5;
+
+    #[test]
+    fn pruning_drops_light_moves_and_empty_positions() {
+        let start = Quoridor5::initial_server();
+        let mut later = start.clone();
+        later.pawn_positions.insert(0, (2, 1).into());
+
+        let mut book = OpeningBook::new();
+        let strong = Move::MovePawn((2, 0).into(), (2, 1).into());
+        let weak = Move::MovePawn((2, 0).into(), (1, 0).into());
+        book.add(&start, strong, 5);
+        book.add(&start, weak, 1);
+        book.add(&later, Move::MovePawn((2, 4).into(), (2, 3).into()), 2);
+
+        book.prune(3);
+        let moves = book.lookup(&start);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].qmove, strong);
+        assert_eq!(moves[0].weight, 5);
+        assert!(book.lookup(&later).is_empty());
+        assert_eq!(book.entries.len(), 1);
+    }
+}
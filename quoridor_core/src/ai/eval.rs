@@ -0,0 +1,114 @@
+use super::*;
+
+pub trait Evaluator<Rb: Rulebook>: Send + Sync {
+    fn evaluate(&self, game: &QGame<Rb>, player: PlayerID) -> i32;
+}
+
+pub fn opponent<Rb: Rulebook>(player: PlayerID) -> PlayerID {
+    (player + 1) % Rb::PLAYER_COUNT
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct PathLength;
+
+impl<Rb: Rulebook> Evaluator<Rb> for PathLength {
+    fn evaluate(&self, game: &QGame<Rb>, player: PlayerID) -> i32 {
+        let own = player_distance(game, player).unwrap_or(u8::MAX) as i32;
+        let other = player_distance(game, opponent::<Rb>(player)).unwrap_or(u8::MAX) as i32;
+        other - own
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct WallsRemaining;
+
+impl<Rb: Rulebook> Evaluator<Rb> for WallsRemaining {
+    fn evaluate(&self, game: &QGame<Rb>, player: PlayerID) -> i32 {
+        game.wall_counts[player as usize] as i32
+            - game.wall_counts[opponent::<Rb>(player) as usize] as i32
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Mobility;
+
+fn pawn_mobility<Rb: Rulebook<Move = Move>>(game: &QGame<Rb>, player: PlayerID) -> i32 {
+    let mut game = Clone::clone(game);
+    game.turn_of = player;
+    game.pawn_positions
+        .iter()
+        .filter(|(&id, _)| pawn_owner::<Rb>(id) == player)
+        .map(|(_, &pawn)| {
+            (0u8..Rb::BOARD_SIZE)
+                .flat_map(|x| (0u8..Rb::BOARD_SIZE).map(move |y| Position::from((x, y))))
+                .filter(|target| {
+                    !game.pawn_positions.contains_right(target)
+                        && Rb::validate_move(&game, Move::MovePawn(pawn, *target)).is_ok()
+                })
+                .count() as i32
+        })
+        .sum()
+}
+
+impl<Rb: Rulebook<Move = Move>> Evaluator<Rb> for Mobility {
+    fn evaluate(&self, game: &QGame<Rb>, player: PlayerID) -> i32 {
+        pawn_mobility(game, player) - pawn_mobility(game, opponent::<Rb>(player))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct OpponentDistance;
+
+impl<Rb: Rulebook> Evaluator<Rb> for OpponentDistance {
+    fn evaluate(&self, game: &QGame<Rb>, player: PlayerID) -> i32 {
+        let other = opponent::<Rb>(player);
+        let pawns = |owner: PlayerID| {
+            game.pawn_positions
+                .iter()
+                .filter(move |(&id, _)| pawn_owner::<Rb>(id) == owner)
+                .map(|(_, &pos)| pos)
+        };
+        pawns(player)
+            .flat_map(|own| {
+                pawns(other).map(move |theirs| {
+                    (own.x as i32 - theirs.x as i32).abs() + (own.y as i32 - theirs.y as i32).abs()
+                })
+            })
+            .min()
+            .map_or(0, |distance| -distance)
+    }
+}
+
+pub struct WeightedSum<Rb: Rulebook> {
+    terms: Vec<(i32, Box<dyn Evaluator<Rb>>)>,
+}
+
+impl<Rb: Rulebook> WeightedSum<Rb> {
+    pub fn new() -> Self {
+        WeightedSum { terms: vec![] }
+    }
+
+    pub fn with<E: Evaluator<Rb> + 'static>(mut self, weight: i32, evaluator: E) -> Self {
+        self.terms.push((weight, Box::new(evaluator)));
+        self
+    }
+}
+
+impl<Rb: Rulebook> Default for WeightedSum<Rb> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Rb: Rulebook> Evaluator<Rb> for WeightedSum<Rb> {
+    fn evaluate(&self, game: &QGame<Rb>, player: PlayerID) -> i32 {
+        self.terms
+            .iter()
+            .map(|(weight, evaluator)| weight * evaluator.evaluate(game, player))
+            .sum()
+    }
+}
+
+pub fn standard_evaluator<Rb: Rulebook>() -> WeightedSum<Rb> {
+    WeightedSum::new().with(10, PathLength).with(1, WallsRemaining)
+}
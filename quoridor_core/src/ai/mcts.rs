@@ -0,0 +1,307 @@
+use super::*;
+use crate::rng::SplitMix64;
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug)]
+pub struct MctsConfig {
+    pub playouts: u32,
+    pub time_limit: Option<Duration>,
+    pub threads: usize,
+    pub exploration: f64,
+    pub max_playout_length: u16,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            playouts: 2000,
+            time_limit: None,
+            threads: 1,
+            exploration: std::f64::consts::SQRT_2,
+            max_playout_length: 300,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Mcts {
+    pub config: MctsConfig,
+    rng: SplitMix64,
+}
+
+struct Node {
+    qmove: Option<Move>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Option<Vec<Move>>,
+    mover: PlayerID,
+    visits: u32,
+    score: f64,
+    finished: bool,
+    winner: Option<PlayerID>,
+}
+
+impl Node {
+    fn new(qmove: Option<Move>, parent: Option<usize>, mover: PlayerID) -> Self {
+        Node {
+            qmove,
+            parent,
+            children: vec![],
+            untried: None,
+            mover,
+            visits: 0,
+            score: 0.0,
+            finished: false,
+            winner: None,
+        }
+    }
+}
+
+fn outcome(result: MoveResult) -> Option<Option<PlayerID>> {
+    match result {
+        MoveResult::Continue => None,
+        MoveResult::Win(id) => Some(Some(id)),
+        _ => Some(None),
+    }
+}
+
+// Legal-move generation validates every wall slot, so playouts sample candidates instead
+fn random_move<Rb: Rulebook<Move = Move>>(game: &QGame<Rb>, rng: &mut SplitMix64) -> Option<Move> {
+    let pawns = game
+        .pawn_positions
+        .iter()
+        .filter(|(&id, _)| pawn_owner::<Rb>(id) == game.turn_of)
+        .map(|(_, &pos)| pos)
+        .collect::<Vec<_>>();
+
+    for _ in 0..64 {
+        let qmove = if pawns.is_empty() || rng.below(10) < 3 {
+            let span = Rb::BOARD_SIZE as u64 + 1;
+            let pos = Position::from((rng.below(span) as u8, rng.below(span) as u8));
+            Move::PlaceWall(if rng.below(2) == 0 {
+                Wall::horizontal(pos)
+            } else {
+                Wall::vertical(pos)
+            })
+        } else {
+            let pawn = pawns[rng.below(pawns.len() as u64) as usize];
+            let last = Rb::BOARD_SIZE as i8 - 1;
+            let target = Position::from((
+                (pawn.x as i8 + rng.below(5) as i8 - 2).max(0).min(last) as u8,
+                (pawn.y as i8 + rng.below(5) as i8 - 2).max(0).min(last) as u8,
+            ));
+            Move::MovePawn(pawn, target)
+        };
+
+        let occupied = match qmove {
+            Move::MovePawn(_, target) => game.pawn_positions.contains_right(&target),
+            Move::PlaceWall(wall) => game.walls.contains(&wall),
+            _ => false,
+        };
+        if !occupied && Rb::validate_move(game, qmove).is_ok() {
+            return Some(qmove);
+        }
+    }
+
+    let moves = legal_moves(game);
+    if moves.is_empty() {
+        None
+    } else {
+        Some(moves[rng.below(moves.len() as u64) as usize])
+    }
+}
+
+fn playout<Rb: Rulebook<Move = Move>>(
+    mut game: QGame<Rb>,
+    config: &MctsConfig,
+    rng: &mut SplitMix64,
+) -> Option<PlayerID> {
+    for _ in 0..config.max_playout_length {
+        let qmove = random_move(&game, rng)?;
+        if let Some(result) = outcome(Rb::apply_move(&mut game, qmove)) {
+            return result;
+        }
+    }
+    None
+}
+
+fn search_tree<Rb: Rulebook<Move = Move>>(
+    root: &QGame<Rb>,
+    config: &MctsConfig,
+    playouts: u32,
+    deadline: Option<Instant>,
+    mut rng: SplitMix64,
+) -> Vec<(Move, u32, f64)> {
+    let mut nodes = vec![Node::new(None, None, root.turn_of)];
+
+    for i in 0..playouts {
+        if i % 32 == 0 && deadline.map_or(false, |deadline| Instant::now() > deadline) {
+            break;
+        }
+
+        let mut game = Clone::clone(root);
+        let mut node = 0;
+
+        // Selection
+        while !nodes[node].finished
+            && nodes[node].untried.as_ref().map_or(false, |u| u.is_empty())
+            && !nodes[node].children.is_empty()
+        {
+            let parent_visits = nodes[node].visits as f64;
+            node = *nodes[node]
+                .children
+                .iter()
+                .max_by(|&&a, &&b| {
+                    let uct = |n: &Node| {
+                        n.score / n.visits as f64
+                            + config.exploration * (parent_visits.ln() / n.visits as f64).sqrt()
+                    };
+                    uct(&nodes[a]).partial_cmp(&uct(&nodes[b])).unwrap()
+                })
+                .unwrap();
+            Rb::apply_move(&mut game, nodes[node].qmove.unwrap());
+        }
+
+        // Expansion
+        if !nodes[node].finished {
+            if nodes[node].untried.is_none() {
+                nodes[node].untried = Some(legal_moves(&game));
+            }
+            let untried = nodes[node].untried.as_mut().unwrap();
+            if !untried.is_empty() {
+                let qmove = untried.swap_remove(rng.below(untried.len() as u64) as usize);
+                let mut child = Node::new(Some(qmove), Some(node), game.turn_of);
+                if let Some(winner) = outcome(Rb::apply_move(&mut game, qmove)) {
+                    child.finished = true;
+                    child.winner = winner;
+                }
+                nodes.push(child);
+                let child = nodes.len() - 1;
+                nodes[node].children.push(child);
+                node = child;
+            }
+        }
+
+        // Simulation
+        let winner = if nodes[node].finished {
+            nodes[node].winner
+        } else {
+            playout(game, config, &mut rng)
+        };
+
+        // Backpropagation
+        let mut current = Some(node);
+        while let Some(n) = current {
+            nodes[n].visits += 1;
+            nodes[n].score += match winner {
+                Some(id) if id == nodes[n].mover => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            current = nodes[n].parent;
+        }
+    }
+
+    nodes[0]
+        .children
+        .iter()
+        .map(|&c| (nodes[c].qmove.unwrap(), nodes[c].visits, nodes[c].score))
+        .collect()
+}
+
+impl Mcts {
+    pub fn new(config: MctsConfig, seed: u64) -> Self {
+        Mcts {
+            config,
+            rng: SplitMix64::new(seed),
+        }
+    }
+
+    pub fn search<Rb: Rulebook<Move = Move>>(&mut self, game: &QGame<Rb>) -> Vec<(Move, u32, f64)> {
+        let deadline = self.config.time_limit.map(|limit| Instant::now() + limit);
+        let threads = self.config.threads.max(1);
+        let playouts = self.config.playouts.max(1);
+
+        let results = if threads == 1 {
+            vec![search_tree(game, &self.config, playouts, deadline, self.rng)]
+        } else {
+            // The remainder is spread over the first threads, and every thread gets at least one playout
+            let share = |i: usize| {
+                let extra = (i < playouts as usize % threads) as u32;
+                (playouts / threads as u32 + extra).max(1)
+            };
+            let handles = (0..threads)
+                .map(|i| {
+                    let game = Clone::clone(game);
+                    let config = self.config;
+                    let rng = SplitMix64::new(self.rng.next_u64());
+                    let playouts = share(i);
+                    std::thread::spawn(move || search_tree(&game, &config, playouts, deadline, rng))
+                })
+                .collect::<Vec<_>>();
+            handles.into_iter().filter_map(|h| h.join().ok()).collect()
+        };
+        self.rng.next_u64();
+
+        let mut merged: HashMap<Move, (u32, f64)> = HashMap::new();
+        for (qmove, visits, score) in results.into_iter().flatten() {
+            let entry = merged.entry(qmove).or_insert((0, 0.0));
+            entry.0 += visits;
+            entry.1 += score;
+        }
+        let mut merged = merged
+            .into_iter()
+            .map(|(qmove, (visits, score))| (qmove, visits, score))
+            .collect::<Vec<_>>();
+        merged.sort_by(|a, b| b.1.cmp(&a.1));
+        merged
+    }
+}
+
+impl<Rb: Rulebook<Move = Move>> Bot<Rb> for Mcts {
+    fn choose_move(&mut self, game: &QGame<Rb>) -> Option<Move> {
+        self.search(game).first().map(|&(qmove, _, _)| qmove)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rulebooks::Quoridor5;
+
+    // Both pawns are a step from their goals, so anything but the winning move loses
+    fn one_step_from_goal() -> QGame<Quoridor5> {
+        let mut game = Quoridor5::initial_server();
+        game.pawn_positions.clear();
+        game.pawn_positions.insert(0, (2, 3).into());
+        game.pawn_positions.insert(1, (0, 1).into());
+        game
+    }
+
+    #[test]
+    fn finds_the_winning_pawn_move() {
+        let config = MctsConfig {
+            playouts: 400,
+            ..MctsConfig::default()
+        };
+        let game = one_step_from_goal();
+        let winning = Move::MovePawn((2, 3).into(), (2, 4).into());
+        assert_eq!(Mcts::new(config, 7).choose_move(&game), Some(winning));
+    }
+
+    #[test]
+    fn more_threads_than_playouts_still_search() {
+        let config = MctsConfig {
+            playouts: 3,
+            threads: 4,
+            ..MctsConfig::default()
+        };
+        let game = one_step_from_goal();
+        let visits = Mcts::new(config, 7)
+            .search(&game)
+            .iter()
+            .map(|&(_, visits, _)| visits)
+            .sum::<u32>();
+        assert_eq!(visits, 4);
+    }
+}
@@ -0,0 +1,316 @@
+use crate::rulebooks::*;
+use crate::*;
+use std::collections::VecDeque;
+use std::error::Error;
+
+pub mod alpha_beta;
+pub mod analysis;
+pub mod book;
+pub mod eval;
+pub mod mcts;
+pub mod profile;
+
+pub use alpha_beta::*;
+pub use analysis::*;
+pub use book::*;
+pub use eval::*;
+pub use mcts::*;
+pub use profile::*;
+
+pub trait Bot<Rb: Rulebook>: Send + Sync {
+    fn choose_move(&mut self, game: &QGame<Rb>) -> Option<Rb::Move>;
+
+    // Why the bot can no longer play, for bots that can break, like external engines
+    fn failure(&self) -> Option<String> {
+        None
+    }
+}
+
+impl<Rb: Rulebook> Bot<Rb> for Box<dyn Bot<Rb>> {
+    fn choose_move(&mut self, game: &QGame<Rb>) -> Option<Rb::Move> {
+        (**self).choose_move(game)
+    }
+
+    fn failure(&self) -> Option<String> {
+        (**self).failure()
+    }
+}
+
+pub fn bot_agent<Rb: Rulebook<Move = Move>, B: Bot<Rb>>(
+    core: AgentCore<QGame<Rb>>,
+    mut bot: B,
+) -> impl Send + Sync + FnMut() -> Result<(), Box<dyn Error>> {
+    let mut game: Option<QGame<Rb>> = None;
+    let mut side = 0;
+    let mut waiting = false;
+    // Moves the server turned down in the current position. A bot that only sees part of the board,
+    // as in fog, can be refused a move it thinks is legal, and would otherwise send it forever.
+    let mut sent = None;
+    let mut rejected = vec![];
+
+    move || {
+        while let Ok(event) = core.event_channel.try_recv() {
+            match event {
+                GameEvent::GameStart(g, s) => {
+                    game = Some(g);
+                    side = s;
+                    waiting = false;
+                    rejected.clear();
+                }
+                GameEvent::MoveHappened(qmove) => {
+                    if let Some(g) = &mut game {
+                        Rb::apply_move(g, qmove);
+                    }
+                    waiting = false;
+                    rejected.clear();
+                }
+                GameEvent::InvalidMove => {
+                    rejected.extend(sent.take());
+                    waiting = false;
+                }
+                GameEvent::OpponentQuit | GameEvent::GameEnd(_) => game = None,
+                _ => {}
+            }
+        }
+
+        if let Some(g) = &game {
+            if !waiting && g.turn_of == side {
+                let choice = bot.choose_move(g);
+                if let Some(failure) = bot.failure() {
+                    return Err(failure.into());
+                }
+                let choice = choice
+                    .filter(|qmove| !rejected.contains(qmove))
+                    .or_else(|| fallback_move(g, &rejected));
+                if let Some(qmove) = choice {
+                    core.move_channel
+                        .send(qmove)
+                        .map_err(|_| "game closed")?;
+                    sent = Some(qmove);
+                    waiting = true;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// The pawn move that gets closest to the goal, or failing that any other move, leaving out `rejected`
+fn fallback_move<Rb: Rulebook<Move = Move>>(game: &QGame<Rb>, rejected: &[Move]) -> Option<Move> {
+    let goal = goal_row::<Rb>(game.turn_of);
+    legal_moves(game)
+        .into_iter()
+        .filter(|qmove| !rejected.contains(qmove))
+        .min_by_key(|qmove| match qmove {
+            Move::MovePawn(_, target) => shortest_path(game, *target, goal).unwrap_or(u8::MAX) as u16,
+            _ => u16::MAX,
+        })
+}
+
+pub fn pawn_owner<Rb: Rulebook>(id: PawnID) -> PlayerID {
+    id / (Rb::PAWN_COUNT / Rb::PLAYER_COUNT)
+}
+
+pub fn goal_row<Rb: Rulebook>(player: PlayerID) -> u8 {
+    if player == 0 {
+        Rb::BOARD_SIZE - 1
+    } else {
+        0
+    }
+}
+
+pub fn shortest_path<Rb: Rulebook>(game: &QGame<Rb>, start: Position, goal_y: u8) -> Option<u8> {
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+    distances.insert(start, 0u8);
+    queue.push_back(start);
+
+    while let Some(pos) = queue.pop_front() {
+        let distance = distances[&pos];
+        if pos.y == goal_y {
+            return Some(distance);
+        }
+
+        if Rb::TOROIDAL {
+            for (next, _) in toroidal_neighbours(game, pos) {
+                if !distances.contains_key(&next) {
+                    distances.insert(next, distance + 1);
+                    queue.push_back(next);
+                }
+            }
+            continue;
+        }
+
+        let mut neighbours = vec![];
+        if pos.x != 0 {
+            neighbours.push(Position::from((pos.x - 1, pos.y)));
+        }
+        if pos.x != Rb::BOARD_SIZE - 1 {
+            neighbours.push(Position::from((pos.x + 1, pos.y)));
+        }
+        if pos.y != 0 {
+            neighbours.push(Position::from((pos.x, pos.y - 1)));
+        }
+        if pos.y != Rb::BOARD_SIZE - 1 {
+            neighbours.push(Position::from((pos.x, pos.y + 1)));
+        }
+
+        for next in neighbours {
+            if !distances.contains_key(&next) && check_movable(game, pos, next, false).is_ok() {
+                distances.insert(next, distance + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+pub fn player_distance<Rb: Rulebook>(game: &QGame<Rb>, player: PlayerID) -> Option<u8> {
+    game.pawn_positions
+        .iter()
+        .filter(|(&id, _)| pawn_owner::<Rb>(id) == player)
+        .filter_map(|(_, &pos)| shortest_path(game, pos, goal_row::<Rb>(player)))
+        .min()
+}
+
+fn wall_candidates(size: u8) -> impl Iterator<Item = Wall> {
+    (0u8..=size).flat_map(move |x| {
+        (0u8..=size).flat_map(move |y| {
+            let pos = Position::from((x, y));
+            vec![Wall::horizontal(pos), Wall::vertical(pos)]
+        })
+    })
+}
+
+pub fn legal_moves<Rb: Rulebook<Move = Move>>(game: &QGame<Rb>) -> Vec<Move> {
+    let mut moves = vec![];
+
+    for (&id, &pawn) in game.pawn_positions.iter() {
+        if pawn_owner::<Rb>(id) != game.turn_of {
+            continue;
+        }
+        for x in 0u8..Rb::BOARD_SIZE {
+            for y in 0u8..Rb::BOARD_SIZE {
+                let target = Position::from((x, y));
+                let qmove = Move::MovePawn(pawn, target);
+                if !game.pawn_positions.contains_right(&target)
+                    && Rb::validate_move(game, qmove).is_ok()
+                {
+                    moves.push(qmove);
+                }
+            }
+        }
+    }
+
+    for wall in game.walls.iter() {
+        let qmove = Move::RemoveWall(*wall);
+        if Rb::validate_move(game, qmove).is_ok() {
+            moves.push(qmove);
+        }
+    }
+
+    let owned_walls = game
+        .wall_owners
+        .iter()
+        .filter(|(_, &owner)| owner == game.turn_of)
+        .map(|(&wall, _)| wall)
+        .collect::<Vec<_>>();
+
+    for wall in wall_candidates(Rb::BOARD_SIZE) {
+        if game.walls.contains(&wall) {
+            continue;
+        }
+        let qmove = Move::PlaceWall(wall);
+        if game.wall_counts[game.turn_of as usize] > 0 && Rb::validate_move(game, qmove).is_ok() {
+            moves.push(qmove);
+        }
+        for &owned in owned_walls.iter() {
+            let qmove = Move::MoveWall(owned, wall);
+            if Rb::validate_move(game, qmove).is_ok() {
+                moves.push(qmove);
+            }
+        }
+    }
+
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Always offers the same wall, like a deterministic search blind to a hidden wall in its way
+    struct Stubborn(Move);
+
+    impl Bot<FogQuoridor> for Stubborn {
+        fn choose_move(&mut self, _: &QGame<FogQuoridor>) -> Option<Move> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn refused_moves_are_not_sent_again() {
+        let (server_events, event_channel) = crossbeam_channel::unbounded();
+        let (move_channel, server_moves) = crossbeam_channel::unbounded();
+        let wall = Move::PlaceWall(Wall::horizontal((4, 4).into()));
+        let mut agent = bot_agent(
+            AgentCore {
+                event_channel,
+                move_channel,
+            },
+            Stubborn(wall),
+        );
+
+        let game = FogQuoridor::initial_server();
+        server_events.send(GameEvent::GameStart(game, 0)).unwrap();
+        agent().unwrap();
+        assert_eq!(server_moves.try_recv(), Ok(wall));
+
+        server_events.send(GameEvent::InvalidMove).unwrap();
+        agent().unwrap();
+        let step = Move::MovePawn((4, 0).into(), (4, 1).into());
+        assert_eq!(server_moves.try_recv(), Ok(step));
+
+        // A new position forgets what was refused
+        server_events.send(GameEvent::MoveHappened(step)).unwrap();
+        server_events
+            .send(GameEvent::MoveHappened(Move::MovePawn(
+                (4, 8).into(),
+                (4, 7).into(),
+            )))
+            .unwrap();
+        agent().unwrap();
+        assert_eq!(server_moves.try_recv(), Ok(wall));
+    }
+
+    struct Broken;
+
+    impl Bot<FogQuoridor> for Broken {
+        fn choose_move(&mut self, _: &QGame<FogQuoridor>) -> Option<Move> {
+            None
+        }
+
+        fn failure(&self) -> Option<String> {
+            Some("engine exited".into())
+        }
+    }
+
+    #[test]
+    fn broken_bots_stop_the_agent() {
+        let (server_events, event_channel) = crossbeam_channel::unbounded();
+        let (move_channel, server_moves) = crossbeam_channel::unbounded();
+        let mut agent = bot_agent(
+            AgentCore {
+                event_channel,
+                move_channel,
+            },
+            Broken,
+        );
+
+        server_events
+            .send(GameEvent::GameStart(FogQuoridor::initial_server(), 0))
+            .unwrap();
+        assert!(agent().is_err());
+        assert!(server_moves.try_recv().is_err());
+    }
+}
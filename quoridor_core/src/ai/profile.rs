@@ -0,0 +1,195 @@
+use super::*;
+use crate::rng::SplitMix64;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Beginner,
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Style {
+    Balanced,
+    Aggressive,
+    Racing,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Tuning {
+    pub limits: SearchLimits,
+    // Root scores are perturbed by up to this much, in tenths of a path step
+    pub randomness: i32,
+    pub blunder_chance: f64,
+}
+
+impl Difficulty {
+    pub fn tuning(self) -> Tuning {
+        let (max_depth, time_limit, randomness, blunder_chance) = match self {
+            Difficulty::Beginner => (1, None, 15, 0.25),
+            Difficulty::Easy => (1, None, 8, 0.1),
+            Difficulty::Medium => (2, None, 3, 0.03),
+            Difficulty::Hard => (3, Some(Duration::from_secs(2)), 0, 0.0),
+            Difficulty::Expert => (4, Some(Duration::from_secs(5)), 0, 0.0),
+        };
+        Tuning {
+            limits: SearchLimits {
+                max_depth,
+                time_limit,
+                node_limit: None,
+            },
+            randomness,
+            blunder_chance,
+        }
+    }
+}
+
+impl Style {
+    pub fn evaluator<Rb: Rulebook<Move = Move>>(self) -> WeightedSum<Rb> {
+        match self {
+            Style::Balanced => standard_evaluator(),
+            // Spending walls is rewarded rather than hoarded
+            Style::Aggressive => WeightedSum::new()
+                .with(10, PathLength)
+                .with(-2, WallsRemaining)
+                .with(1, OpponentDistance),
+            Style::Racing => WeightedSum::new()
+                .with(12, PathLength)
+                .with(4, WallsRemaining)
+                .with(1, Mobility),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match &s.to_lowercase()[..] {
+            "beginner" => Ok(Difficulty::Beginner),
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            "expert" => Ok(Difficulty::Expert),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for Style {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match &s.to_lowercase()[..] {
+            "balanced" => Ok(Style::Balanced),
+            "aggressive" => Ok(Style::Aggressive),
+            "racing" => Ok(Style::Racing),
+            _ => Err(()),
+        }
+    }
+}
+
+// Roughly what a browser tab searches in a fraction of a second
+pub const DEPTH_ONLY_NODES: u64 = 20_000;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct BotConfig {
+    pub difficulty: Difficulty,
+    pub style: Style,
+    pub seed: u64,
+    // Swaps the time limit for a node budget, for platforms without a clock such as wasm
+    #[serde(default)]
+    pub depth_only: bool,
+}
+
+impl BotConfig {
+    pub fn new(difficulty: Difficulty, style: Style, seed: u64) -> Self {
+        BotConfig {
+            difficulty,
+            style,
+            seed,
+            depth_only: false,
+        }
+    }
+
+    pub fn depth_only(self) -> Self {
+        BotConfig {
+            depth_only: true,
+            ..self
+        }
+    }
+
+    pub fn parse(difficulty: &str, style: Option<&str>, seed: u64) -> Result<Self, ()> {
+        let style = match style {
+            Some(s) if !s.is_empty() => s.parse()?,
+            _ => Style::Balanced,
+        };
+        Ok(Self::new(difficulty.parse()?, style, seed))
+    }
+
+    pub fn build<Rb: Rulebook<Move = Move>>(&self) -> ConfiguredBot<Rb> {
+        let mut tuning = self.difficulty.tuning();
+        if self.depth_only {
+            tuning.limits.time_limit = None;
+            tuning.limits.node_limit = Some(DEPTH_ONLY_NODES);
+        }
+        ConfiguredBot {
+            engine: AlphaBeta::with_evaluator(tuning.limits, self.style.evaluator()),
+            tuning,
+            rng: SplitMix64::new(self.seed),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ConfiguredBot<Rb: Rulebook> {
+    pub engine: AlphaBeta<Rb>,
+    pub tuning: Tuning,
+    rng: SplitMix64,
+}
+
+impl<Rb: Rulebook<Move = Move>> ConfiguredBot<Rb> {
+    fn blunder(&mut self, game: &QGame<Rb>) -> Option<Move> {
+        let moves = legal_moves(game);
+        if moves.is_empty() {
+            None
+        } else {
+            Some(moves[self.rng.below(moves.len() as u64) as usize])
+        }
+    }
+}
+
+impl<Rb: Rulebook<Move = Move>> Bot<Rb> for ConfiguredBot<Rb> {
+    fn choose_move(&mut self, game: &QGame<Rb>) -> Option<Move> {
+        if self.rng.next_f64() < self.tuning.blunder_chance {
+            return self.blunder(game);
+        }
+
+        if self.tuning.randomness == 0 {
+            return self
+                .engine
+                .search(game)
+                .map(|result| result.best_move)
+                .or_else(|| self.blunder(game));
+        }
+
+        let randomness = self.tuning.randomness;
+        let depth = self.tuning.limits.max_depth;
+        let scored = self.engine.root_scores(game, depth);
+        let rng = &mut self.rng;
+        scored
+            .into_iter()
+            .map(|(qmove, score, _)| {
+                let noise = rng.below(randomness as u64 * 2 + 1) as i32 - randomness;
+                (score + noise, qmove)
+            })
+            .max_by_key(|&(score, _)| score)
+            .map(|(_, qmove)| qmove)
+            .or_else(|| self.blunder(game))
+    }
+}
@@ -0,0 +1,157 @@
+use crate::record::GameRecord;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::PathBuf;
+use tbmp_core::PlayerID;
+
+// Finished games kept by the servers, one JSON file per game in a directory. A game's id is the
+// time it ended and a counter, so ids sort by age and stay unique across restarts.
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArchivedGame {
+    pub id: String,
+    pub record: GameRecord,
+    pub options: Vec<(String, String)>,
+    // Seconds since the Unix epoch
+    pub started: u64,
+    pub ended: u64,
+}
+
+// What a listing shows of a game: everything but the moves themselves
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Summary {
+    pub id: String,
+    pub rulebook: String,
+    pub players: Vec<String>,
+    pub options: Vec<(String, String)>,
+    pub winner: Option<PlayerID>,
+    pub moves: usize,
+    pub started: u64,
+    pub ended: u64,
+}
+
+impl From<&ArchivedGame> for Summary {
+    fn from(game: &ArchivedGame) -> Self {
+        Summary {
+            id: game.id.clone(),
+            rulebook: game.record.rulebook.clone(),
+            players: game.record.players.clone(),
+            options: game.options.clone(),
+            winner: game.record.winner,
+            moves: game.record.moves.len(),
+            started: game.started,
+            ended: game.ended,
+        }
+    }
+}
+
+// Narrows a listing; unset fields match every game
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Query {
+    pub rulebook: Option<String>,
+    pub player: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl Query {
+    fn matches(&self, game: &ArchivedGame) -> bool {
+        self.rulebook.as_ref().map_or(true, |rulebook| game.record.rulebook == *rulebook)
+            && self
+                .player
+                .as_ref()
+                .map_or(true, |player| game.record.players.contains(player))
+    }
+}
+
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[derive(Clone, Debug)]
+pub struct Archive {
+    dir: PathBuf,
+}
+
+impl Archive {
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Could not open archive {}: {}", dir.display(), e))?;
+        Ok(Archive { dir })
+    }
+
+    // Writes a finished game and returns its id
+    pub fn store(
+        &self,
+        record: GameRecord,
+        options: Vec<(String, String)>,
+        started: u64,
+    ) -> Result<String, Box<dyn Error>> {
+        let ended = now();
+        for n in 1.. {
+            let id = format!("{}-{}", ended, n);
+            let file = match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.dir.join(format!("{}.json", id)))
+            {
+                Ok(file) => file,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let game = ArchivedGame {
+                id,
+                record,
+                options,
+                started,
+                ended,
+            };
+            let mut out = BufWriter::new(file);
+            serde_json::to_writer_pretty(&mut out, &game)?;
+            out.flush()?;
+            return Ok(game.id);
+        }
+        unreachable!()
+    }
+
+    pub fn fetch(&self, id: &str) -> Result<ArchivedGame, Box<dyn Error>> {
+        // Ids come from clients, so anything that could leave the directory is refused
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return Err(format!("No archived game {}", id).into());
+        }
+        let file = fs::File::open(self.dir.join(format!("{}.json", id)))
+            .map_err(|_| format!("No archived game {}", id))?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    // Newest first. Files that cannot be read, such as a game still being written, are skipped.
+    pub fn list(&self, query: &Query) -> Result<Vec<Summary>, Box<dyn Error>> {
+        let mut games = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |extension| extension != "json") {
+                continue;
+            }
+            let game: ArchivedGame = match fs::File::open(&path)
+                .map_err(Box::<dyn Error>::from)
+                .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?))
+            {
+                Ok(game) => game,
+                Err(_) => continue,
+            };
+            if query.matches(&game) {
+                games.push(Summary::from(&game));
+            }
+        }
+        games.sort_by_key(|game| {
+            let counter = game.id.rsplit('-').next().and_then(|n| n.parse::<u64>().ok());
+            std::cmp::Reverse((game.ended, counter))
+        });
+        games.truncate(query.limit.unwrap_or(usize::MAX));
+        Ok(games)
+    }
+}
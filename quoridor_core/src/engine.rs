@@ -0,0 +1,519 @@
+use crate::ai::{bot_agent, Bot};
+use crate::notation::*;
+use crate::*;
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::time::{Duration, Instant};
+
+// A line-based engine protocol modelled on UCI. The controller sends:
+//   qei                                  -> id name/author lines, then qeiok
+//   isready                              -> readyok
+//   setoption name <NAME> value <VALUE>     (the Rulebook option names the rules in use)
+//   newgame
+//   position startpos|qfen <POSITION> [moves <MOVE>...]
+//   go [depth N] [movetime MS] [wtime MS] [btime MS]
+//                                        -> info lines, then bestmove <MOVE>|none
+//   quit
+// Moves and positions use the notation module.
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GoLimits {
+    pub depth: Option<u8>,
+    pub movetime: Option<u64>,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+}
+
+impl GoLimits {
+    // Time the engine should spend on this move, given whose clock is running
+    pub fn budget(&self, turn_of: PlayerID) -> Option<Duration> {
+        let remaining = if turn_of == 0 { self.wtime } else { self.btime };
+        match (self.movetime, remaining) {
+            (Some(ms), _) => Some(Duration::from_millis(ms)),
+            (None, Some(ms)) => Some(Duration::from_millis(ms / 20)),
+            (None, None) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngineCommand {
+    Qei,
+    IsReady,
+    SetOption { name: String, value: String },
+    NewGame,
+    Position { qfen: Option<String>, moves: Vec<String> },
+    Go(GoLimits),
+    Quit,
+}
+
+impl EngineCommand {
+    pub fn parse(line: &str) -> Result<Self, ()> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["qei"] => Ok(EngineCommand::Qei),
+            ["isready"] => Ok(EngineCommand::IsReady),
+            ["newgame"] => Ok(EngineCommand::NewGame),
+            ["quit"] => Ok(EngineCommand::Quit),
+            ["setoption", "name", rest @ ..] => {
+                let split = rest.iter().position(|&w| w == "value").ok_or(())?;
+                Ok(EngineCommand::SetOption {
+                    name: rest[..split].join(" "),
+                    value: rest[split + 1..].join(" "),
+                })
+            }
+            ["position", rest @ ..] => {
+                let split = rest.iter().position(|&w| w == "moves").unwrap_or(rest.len());
+                let qfen = match &rest[..split] {
+                    ["startpos"] => None,
+                    ["qfen", position @ ..] => Some(position.join(" ")),
+                    _ => return Err(()),
+                };
+                let moves = rest[split..].iter().skip(1).map(|&m| m.into()).collect();
+                Ok(EngineCommand::Position { qfen, moves })
+            }
+            ["go", rest @ ..] => {
+                let mut limits = GoLimits::default();
+                for pair in rest.chunks(2) {
+                    match pair {
+                        ["depth", n] => limits.depth = Some(n.parse().map_err(|_| ())?),
+                        ["movetime", ms] => limits.movetime = Some(ms.parse().map_err(|_| ())?),
+                        ["wtime", ms] => limits.wtime = Some(ms.parse().map_err(|_| ())?),
+                        ["btime", ms] => limits.btime = Some(ms.parse().map_err(|_| ())?),
+                        _ => return Err(()),
+                    }
+                }
+                Ok(EngineCommand::Go(limits))
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::fmt::Display for EngineCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineCommand::Qei => write!(f, "qei"),
+            EngineCommand::IsReady => write!(f, "isready"),
+            EngineCommand::NewGame => write!(f, "newgame"),
+            EngineCommand::Quit => write!(f, "quit"),
+            EngineCommand::SetOption { name, value } => {
+                write!(f, "setoption name {} value {}", name, value)
+            }
+            EngineCommand::Position { qfen, moves } => {
+                match qfen {
+                    Some(qfen) => write!(f, "position qfen {}", qfen)?,
+                    None => write!(f, "position startpos")?,
+                }
+                if !moves.is_empty() {
+                    write!(f, " moves {}", moves.join(" "))?;
+                }
+                Ok(())
+            }
+            EngineCommand::Go(limits) => {
+                write!(f, "go")?;
+                let fields = [
+                    ("depth", limits.depth.map(u64::from)),
+                    ("movetime", limits.movetime),
+                    ("wtime", limits.wtime),
+                    ("btime", limits.btime),
+                ];
+                for (name, value) in fields.iter() {
+                    if let Some(value) = value {
+                        write!(f, " {} {}", name, value)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngineReply {
+    Id { key: String, value: String },
+    QeiOk,
+    ReadyOk,
+    Info { depth: u8, score: i32, nodes: u64, pv: Vec<String> },
+    BestMove(Option<String>),
+}
+
+impl EngineReply {
+    pub fn parse(line: &str) -> Result<Self, ()> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["qeiok"] => Ok(EngineReply::QeiOk),
+            ["readyok"] => Ok(EngineReply::ReadyOk),
+            ["bestmove", "none", ..] => Ok(EngineReply::BestMove(None)),
+            ["bestmove", qmove, ..] => Ok(EngineReply::BestMove(Some((*qmove).into()))),
+            ["id", key, value @ ..] => Ok(EngineReply::Id {
+                key: (*key).into(),
+                value: value.join(" "),
+            }),
+            ["info", rest @ ..] => {
+                let (mut depth, mut score, mut nodes, mut pv) = (0, 0, 0, vec![]);
+                let mut words = rest.iter();
+                while let Some(&word) = words.next() {
+                    match word {
+                        "depth" => depth = words.next().ok_or(())?.parse().map_err(|_| ())?,
+                        "score" => score = words.next().ok_or(())?.parse().map_err(|_| ())?,
+                        "nodes" => nodes = words.next().ok_or(())?.parse().map_err(|_| ())?,
+                        "pv" => pv = words.by_ref().map(|&m| m.into()).collect(),
+                        "string" => break,
+                        // Unknown info fields are skipped along with their value
+                        _ => {
+                            words.next();
+                        }
+                    }
+                }
+                Ok(EngineReply::Info {
+                    depth,
+                    score,
+                    nodes,
+                    pv,
+                })
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::fmt::Display for EngineReply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineReply::Id { key, value } => write!(f, "id {} {}", key, value),
+            EngineReply::QeiOk => write!(f, "qeiok"),
+            EngineReply::ReadyOk => write!(f, "readyok"),
+            EngineReply::Info {
+                depth,
+                score,
+                nodes,
+                pv,
+            } => write!(
+                f,
+                "info depth {} score {} nodes {} pv {}",
+                depth,
+                score,
+                nodes,
+                pv.join(" ")
+            ),
+            EngineReply::BestMove(Some(qmove)) => write!(f, "bestmove {}", qmove),
+            EngineReply::BestMove(None) => write!(f, "bestmove none"),
+        }
+    }
+}
+
+// Replays a position command, rejecting illegal moves
+pub fn setup_position<Rb: Rulebook<Move = Move>>(
+    qfen: Option<&str>,
+    moves: &[String],
+) -> Result<QGame<Rb>, ()> {
+    let mut game = match qfen {
+        Some(qfen) => parse_position(qfen)?,
+        None => Rb::initial_server(),
+    };
+    for text in moves {
+        let qmove = parse_move(&game, text)?;
+        Rb::validate_move(&game, qmove)?;
+        Rb::apply_move(&mut game, qmove);
+    }
+    Ok(game)
+}
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Runs an external engine as a subprocess and asks it for a move whenever it is its turn
+pub struct EngineBot<Rb> {
+    pub name: String,
+    pub limits: GoLimits,
+    // How long past its time budget the engine may take before it is given up on
+    pub grace: Duration,
+    child: Child,
+    input: ChildStdin,
+    output: Receiver<String>,
+    failure: Option<String>,
+    _rulebook: PhantomData<fn() -> Rb>,
+}
+
+impl<Rb: Rulebook<Move = Move>> EngineBot<Rb> {
+    pub fn spawn(command: &str, rulebook: &str, limits: GoLimits) -> Result<Self, Box<dyn Error>> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("empty engine command")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let input = child.stdin.take().ok_or("engine has no stdin")?;
+        let stdout = child.stdout.take().ok_or("engine has no stdout")?;
+        let (lines, output) = unbounded();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if lines.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut bot = EngineBot {
+            name: program.into(),
+            limits,
+            grace: Duration::from_secs(5),
+            child,
+            input,
+            output,
+            failure: None,
+            _rulebook: PhantomData,
+        };
+
+        bot.send(EngineCommand::Qei)?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            match bot.receive(deadline)? {
+                EngineReply::Id { key, value } if key == "name" => bot.name = value,
+                EngineReply::QeiOk => break,
+                _ => {}
+            }
+        }
+        bot.send(EngineCommand::SetOption {
+            name: "Rulebook".into(),
+            value: rulebook.into(),
+        })?;
+        bot.send(EngineCommand::NewGame)?;
+        bot.send(EngineCommand::IsReady)?;
+        while bot.receive(deadline)? != EngineReply::ReadyOk {}
+        Ok(bot)
+    }
+
+    fn send(&mut self, command: EngineCommand) -> Result<(), Box<dyn Error>> {
+        writeln!(self.input, "{}", command)?;
+        self.input.flush()?;
+        Ok(())
+    }
+
+    // Waits for the next line the engine sends that follows the protocol
+    fn receive(&mut self, deadline: Instant) -> Result<EngineReply, Box<dyn Error>> {
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.output.recv_timeout(timeout) {
+                Ok(line) => {
+                    if let Ok(reply) = EngineReply::parse(&line) {
+                        return Ok(reply);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => return Err("engine timed out".into()),
+                Err(RecvTimeoutError::Disconnected) => return Err("engine exited".into()),
+            }
+        }
+    }
+
+    fn request_move(&mut self, game: &QGame<Rb>) -> Result<Option<Move>, Box<dyn Error>> {
+        self.send(EngineCommand::Position {
+            qfen: Some(position_to_string(game).map_err(|_| "position has no notation")?),
+            moves: vec![],
+        })?;
+        self.send(EngineCommand::Go(self.limits.clone()))?;
+
+        let deadline = Instant::now()
+            + self.limits.budget(game.turn_of).unwrap_or_default()
+            + self.grace;
+        loop {
+            if let EngineReply::BestMove(qmove) = self.receive(deadline)? {
+                return match qmove {
+                    Some(text) => Ok(Some(
+                        parse_move(game, &text)
+                            .map_err(|_| format!("unreadable move {}", text))?,
+                    )),
+                    None => Ok(None),
+                };
+            }
+        }
+    }
+}
+
+impl<Rb: Rulebook<Move = Move>> Bot<Rb> for EngineBot<Rb> {
+    fn choose_move(&mut self, game: &QGame<Rb>) -> Option<Move> {
+        if self.failure.is_some() {
+            return None;
+        }
+        match self.request_move(game) {
+            Ok(qmove) => qmove,
+            Err(e) => {
+                // Stdout may be carrying a protocol, so this goes to stderr
+                eprintln!("Engine {} failed: {}", self.name, e);
+                self.failure = Some(format!("engine {} failed: {}", self.name, e));
+                None
+            }
+        }
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.failure.clone()
+    }
+}
+
+impl<Rb> Drop for EngineBot<Rb> {
+    fn drop(&mut self) {
+        writeln!(self.input, "{}", EngineCommand::Quit).ok();
+        self.input.flush().ok();
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+pub fn engine_agent<Rb: Rulebook<Move = Move>>(
+    core: AgentCore<QGame<Rb>>,
+    command: &str,
+    rulebook: &str,
+    limits: GoLimits,
+) -> Result<impl Send + Sync + FnMut() -> Result<(), Box<dyn Error>>, Box<dyn Error>> {
+    Ok(bot_agent(core, EngineBot::<Rb>::spawn(command, rulebook, limits)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rulebooks::StandardQuoridor;
+
+    #[test]
+    fn replies_parse() {
+        assert_eq!(
+            EngineReply::parse("bestmove e2"),
+            Ok(EngineReply::BestMove(Some("e2".into())))
+        );
+        assert_eq!(
+            EngineReply::parse("bestmove none"),
+            Ok(EngineReply::BestMove(None))
+        );
+        assert_eq!(
+            EngineReply::parse("bestmove e3h ponder e8"),
+            Ok(EngineReply::BestMove(Some("e3h".into())))
+        );
+        assert_eq!(
+            EngineReply::parse("info depth 3 score -12 nodes 400 pv e2 e8 e3h"),
+            Ok(EngineReply::Info {
+                depth: 3,
+                score: -12,
+                nodes: 400,
+                pv: vec!["e2".into(), "e8".into(), "e3h".into()],
+            })
+        );
+        assert_eq!(
+            EngineReply::parse("id name Some Engine"),
+            Ok(EngineReply::Id {
+                key: "name".into(),
+                value: "Some Engine".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn info_skips_what_it_does_not_know() {
+        assert_eq!(
+            EngineReply::parse("info seldepth 7 depth 2 hashfull 10 score 5 string depth 9"),
+            Ok(EngineReply::Info {
+                depth: 2,
+                score: 5,
+                nodes: 0,
+                pv: vec![],
+            })
+        );
+        assert_eq!(EngineReply::parse("info depth x"), Err(()));
+        assert_eq!(EngineReply::parse("info score"), Err(()));
+        assert_eq!(EngineReply::parse("bestmove"), Err(()));
+        assert_eq!(EngineReply::parse(""), Err(()));
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        let replies = [
+            EngineReply::QeiOk,
+            EngineReply::ReadyOk,
+            EngineReply::BestMove(Some("d4v".into())),
+            EngineReply::BestMove(None),
+            EngineReply::Info {
+                depth: 4,
+                score: 100,
+                nodes: 12345,
+                pv: vec!["e2".into(), "e8".into()],
+            },
+        ];
+        for reply in replies.iter() {
+            assert_eq!(EngineReply::parse(&reply.to_string()).as_ref(), Ok(reply));
+        }
+
+        let commands = [
+            EngineCommand::Qei,
+            EngineCommand::SetOption {
+                name: "Rulebook".into(),
+                value: "StandardQuoridor".into(),
+            },
+            EngineCommand::Position {
+                qfen: None,
+                moves: vec!["e2".into(), "e8".into()],
+            },
+            EngineCommand::Position {
+                qfen: Some("e2,e9 e3h:0 9,10 1".into()),
+                moves: vec![],
+            },
+            EngineCommand::Go(GoLimits {
+                depth: Some(3),
+                movetime: Some(250),
+                ..Default::default()
+            }),
+            EngineCommand::Quit,
+        ];
+        for command in commands.iter() {
+            assert_eq!(
+                EngineCommand::parse(&command.to_string()).as_ref(),
+                Ok(command)
+            );
+        }
+        assert_eq!(EngineCommand::parse("go depth"), Err(()));
+        assert_eq!(EngineCommand::parse("position"), Err(()));
+    }
+
+    #[test]
+    fn positions_replay_legal_moves_only() {
+        let moves = ["e2", "e8", "e3h"]
+            .iter()
+            .map(|&m| m.into())
+            .collect::<Vec<String>>();
+        let game = setup_position::<StandardQuoridor>(None, &moves).unwrap();
+        assert_eq!(
+            notation::position_to_string(&game),
+            Ok("e2,e8 e3h:0 9,10 1".into())
+        );
+
+        let illegal = vec!["e3".into()];
+        assert!(setup_position::<StandardQuoridor>(None, &illegal).is_err());
+    }
+
+    #[test]
+    fn budgets_follow_the_running_clock() {
+        let limits = GoLimits {
+            wtime: Some(20_000),
+            btime: Some(4_000),
+            ..Default::default()
+        };
+        assert_eq!(limits.budget(0), Some(Duration::from_millis(1000)));
+        assert_eq!(limits.budget(1), Some(Duration::from_millis(200)));
+        assert_eq!(GoLimits::default().budget(0), None);
+    }
+}
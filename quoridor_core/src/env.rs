@@ -0,0 +1,446 @@
+use crate::ai::legal_moves;
+use crate::*;
+
+// A gym-style environment for two-player rulebooks with one pawn each.
+// Observations and actions are seen from the side to move: for player 1 the board is flipped
+// vertically, so the mover's goal is always the last row.
+//
+// Actions: one per target cell for the pawn, then one per horizontal and one per vertical wall slot,
+// 81 + 64 + 64 = 209 on the standard board.
+// Observation planes, each BOARD_SIZE x BOARD_SIZE: own pawn, opponent pawn, horizontal walls,
+// vertical walls, own walls left and opponent walls left (both as fractions of the starting count).
+// Wall planes mark the wall with its midpoint south-west of cell (x, y) at index (x, y).
+
+pub const OBSERVATION_PLANES: usize = 6;
+
+fn wall_columns<Rb: Rulebook>() -> usize {
+    if Rb::TOROIDAL {
+        Rb::BOARD_SIZE as usize
+    } else {
+        Rb::BOARD_SIZE as usize - 1
+    }
+}
+
+fn wall_slots<Rb: Rulebook>() -> usize {
+    wall_columns::<Rb>() * (Rb::BOARD_SIZE as usize - 1)
+}
+
+pub fn action_count<Rb: Rulebook>() -> usize {
+    let size = Rb::BOARD_SIZE as usize;
+    size * size + 2 * wall_slots::<Rb>()
+}
+
+pub fn observation_shape<Rb: Rulebook>() -> [usize; 3] {
+    let size = Rb::BOARD_SIZE as usize;
+    [OBSERVATION_PLANES, size, size]
+}
+
+fn flip_position<Rb: Rulebook>(pos: Position, player: PlayerID) -> Position {
+    if player == 0 {
+        pos
+    } else {
+        Position::from((pos.x, Rb::BOARD_SIZE - 1 - pos.y))
+    }
+}
+
+fn flip_wall<Rb: Rulebook>(wall: Wall, player: PlayerID) -> Option<Wall> {
+    if player == 0 {
+        Some(wall)
+    } else {
+        Some(Wall {
+            position: Position::from((wall.position.x, Rb::BOARD_SIZE.checked_sub(wall.position.y)?)),
+            ..wall
+        })
+    }
+}
+
+// The column and row of a wall's slot; FreeQuoridor takes walls, e.g. on the board edge, that have none
+fn wall_slot<Rb: Rulebook>(wall: Wall) -> Option<(usize, usize)> {
+    let column = if Rb::TOROIDAL {
+        wall.position.x as usize
+    } else {
+        (wall.position.x as usize).checked_sub(1)?
+    };
+    let row = (wall.position.y as usize).checked_sub(1)?;
+    if column < wall_columns::<Rb>() && row < Rb::BOARD_SIZE as usize - 1 {
+        Some((column, row))
+    } else {
+        None
+    }
+}
+
+fn own_pawn<Rb: Rulebook>(game: &QGame<Rb>) -> Option<Position> {
+    game.pawn_positions.get_by_left(&game.turn_of).copied()
+}
+
+pub fn move_to_action<Rb: Rulebook>(game: &QGame<Rb>, qmove: Move) -> Option<usize> {
+    let size = Rb::BOARD_SIZE as usize;
+    let player = game.turn_of;
+    match qmove {
+        Move::MovePawn(_, target) => {
+            let target = flip_position::<Rb>(target, player);
+            Some(target.y as usize * size + target.x as usize)
+        }
+        Move::PlaceWall(wall) => {
+            let wall = flip_wall::<Rb>(wall, player)?;
+            let (column, row) = wall_slot::<Rb>(wall)?;
+            let slot = row * wall_columns::<Rb>() + column;
+            Some(match wall.orientation {
+                Orientation::Horizontal => size * size + slot,
+                Orientation::Vertical => size * size + wall_slots::<Rb>() + slot,
+            })
+        }
+        _ => None,
+    }
+}
+
+pub fn action_to_move<Rb: Rulebook>(game: &QGame<Rb>, action: usize) -> Option<Move> {
+    let size = Rb::BOARD_SIZE as usize;
+    let player = game.turn_of;
+    if action < size * size {
+        let target = Position::from(((action % size) as u8, (action / size) as u8));
+        return Some(Move::MovePawn(
+            own_pawn(game)?,
+            flip_position::<Rb>(target, player),
+        ));
+    }
+
+    let slot = action - size * size;
+    let (slot, horizontal) = if slot < wall_slots::<Rb>() {
+        (slot, true)
+    } else if slot < 2 * wall_slots::<Rb>() {
+        (slot - wall_slots::<Rb>(), false)
+    } else {
+        return None;
+    };
+    let column = slot % wall_columns::<Rb>();
+    let x = if Rb::TOROIDAL { column } else { column + 1 };
+    let position = Position::from((x as u8, (slot / wall_columns::<Rb>()) as u8 + 1));
+    let wall = if horizontal {
+        Wall::horizontal(position)
+    } else {
+        Wall::vertical(position)
+    };
+    Some(Move::PlaceWall(flip_wall::<Rb>(wall, player)?))
+}
+
+pub fn action_mask<Rb: Rulebook<Move = Move>>(game: &QGame<Rb>) -> Vec<bool> {
+    let mut mask = vec![false; action_count::<Rb>()];
+    for qmove in legal_moves(game) {
+        if let Some(action) = move_to_action(game, qmove) {
+            mask[action] = true;
+        }
+    }
+    mask
+}
+
+pub fn observe<Rb: Rulebook>(game: &QGame<Rb>, starting_walls: &[u8]) -> Vec<f32> {
+    let size = Rb::BOARD_SIZE as usize;
+    let plane = size * size;
+    let player = game.turn_of;
+    let mut observation = vec![0.0; OBSERVATION_PLANES * plane];
+
+    for (&id, &pos) in game.pawn_positions.iter() {
+        let pos = flip_position::<Rb>(pos, player);
+        let channel = if id == player { 0 } else { 1 };
+        observation[channel * plane + pos.y as usize * size + pos.x as usize] = 1.0;
+    }
+
+    for &wall in game.walls.iter() {
+        let wall = match flip_wall::<Rb>(wall, player) {
+            Some(wall) => wall,
+            None => continue,
+        };
+        let (column, row) = match wall_slot::<Rb>(wall) {
+            Some(slot) => slot,
+            None => continue,
+        };
+        let channel = match wall.orientation {
+            Orientation::Horizontal => 2,
+            Orientation::Vertical => 3,
+        };
+        observation[channel * plane + row * size + column] = 1.0;
+    }
+
+    for (channel, owner) in [(4, player), (5, 1 - player)].iter() {
+        let start = starting_walls[*owner as usize].max(1) as f32;
+        let left = game.wall_counts[*owner as usize] as f32 / start;
+        for value in observation[channel * plane..(channel + 1) * plane].iter_mut() {
+            *value = left;
+        }
+    }
+    observation
+}
+
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub observation: Vec<f32>,
+    pub mask: Vec<bool>,
+    // From the point of view of the player who took the action
+    pub reward: f32,
+    pub done: bool,
+    // Set when the game was cut off at the ply limit rather than won
+    pub truncated: bool,
+}
+
+#[derive(Clone)]
+pub struct QuoridorEnv<Rb: Rulebook> {
+    pub max_plies: usize,
+    game: QGame<Rb>,
+    starting_walls: Vec<u8>,
+    plies: usize,
+    done: bool,
+}
+
+impl<Rb: Rulebook<Move = Move>> QuoridorEnv<Rb> {
+    pub fn new(max_plies: usize) -> Result<Self, ()> {
+        if Rb::PLAYER_COUNT != 2 || Rb::PAWN_COUNT != 2 {
+            return Err(());
+        }
+        let game = Rb::initial_server();
+        Ok(QuoridorEnv {
+            max_plies,
+            starting_walls: game.wall_counts.clone(),
+            game,
+            plies: 0,
+            done: false,
+        })
+    }
+
+    pub fn reset(&mut self) -> Vec<f32> {
+        self.reset_to(Rb::initial_server())
+    }
+
+    // Starts from a given position, e.g. to keep randomised setups reproducible
+    pub fn reset_to(&mut self, game: QGame<Rb>) -> Vec<f32> {
+        self.starting_walls = game.wall_counts.clone();
+        self.game = game;
+        self.plies = 0;
+        self.done = false;
+        self.observation()
+    }
+
+    pub fn game(&self) -> &QGame<Rb> {
+        &self.game
+    }
+
+    pub fn done(&self) -> bool {
+        self.done
+    }
+
+    pub fn observation(&self) -> Vec<f32> {
+        observe(&self.game, &self.starting_walls)
+    }
+
+    pub fn action_mask(&self) -> Vec<bool> {
+        if self.done {
+            vec![false; action_count::<Rb>()]
+        } else {
+            action_mask(&self.game)
+        }
+    }
+
+    // Fails without changing anything if the game is over or the action is illegal
+    pub fn step(&mut self, action: usize) -> Result<Step, ()> {
+        if self.done {
+            return Err(());
+        }
+        let qmove = action_to_move(&self.game, action).ok_or(())?;
+        Rb::validate_move(&self.game, qmove)?;
+
+        let mover = self.game.turn_of;
+        let result = Rb::apply_move(&mut self.game, qmove);
+        self.plies += 1;
+
+        let (reward, won) = match result {
+            MoveResult::Win(id) if id == mover => (1.0, true),
+            MoveResult::Win(_) => (-1.0, true),
+            _ => (0.0, false),
+        };
+        let truncated = !won && self.plies >= self.max_plies;
+        self.done = won || truncated;
+
+        Ok(Step {
+            observation: self.observation(),
+            mask: self.action_mask(),
+            reward,
+            done: self.done,
+            truncated,
+        })
+    }
+}
+
+// Steps many environments at once; observations and masks are concatenated in environment order
+#[derive(Clone)]
+pub struct VecEnv<Rb: Rulebook> {
+    pub envs: Vec<QuoridorEnv<Rb>>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VecStep {
+    pub observations: Vec<f32>,
+    pub masks: Vec<bool>,
+    pub rewards: Vec<f32>,
+    pub dones: Vec<bool>,
+    pub truncated: Vec<bool>,
+}
+
+impl<Rb: Rulebook<Move = Move>> VecEnv<Rb> {
+    pub fn new(count: usize, max_plies: usize) -> Result<Self, ()> {
+        Ok(VecEnv {
+            envs: (0..count)
+                .map(|_| QuoridorEnv::new(max_plies))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn reset(&mut self) -> Vec<f32> {
+        self.envs.iter_mut().flat_map(|env| env.reset()).collect()
+    }
+
+    pub fn action_masks(&self) -> Vec<bool> {
+        self.envs.iter().flat_map(|env| env.action_mask()).collect()
+    }
+
+    // Finished environments are reset, so the returned observations start their next game;
+    // fails with the index of the first environment given an illegal action
+    pub fn step(&mut self, actions: &[usize]) -> Result<VecStep, usize> {
+        if actions.len() != self.envs.len() {
+            return Err(actions.len().min(self.envs.len()));
+        }
+        for (i, (env, &action)) in self.envs.iter().zip(actions).enumerate() {
+            let legal = action_to_move(&env.game, action)
+                .map_or(false, |qmove| Rb::validate_move(&env.game, qmove).is_ok());
+            if env.done || !legal {
+                return Err(i);
+            }
+        }
+
+        let mut batch = VecStep::default();
+        for (i, (env, &action)) in self.envs.iter_mut().zip(actions).enumerate() {
+            let mut step = env.step(action).map_err(|_| i)?;
+            if step.done {
+                step.observation = env.reset();
+                step.mask = env.action_mask();
+            }
+            batch.observations.extend(step.observation);
+            batch.masks.extend(step.mask);
+            batch.rewards.push(step.reward);
+            batch.dones.push(step.done);
+            batch.truncated.push(step.truncated);
+        }
+        Ok(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rulebooks::*;
+
+    fn assert_round_trips<Rb: Rulebook<Move = Move>>(game: &QGame<Rb>) {
+        for qmove in legal_moves(game) {
+            let action = move_to_action(game, qmove).unwrap();
+            assert!(action < action_count::<Rb>());
+            assert_eq!(action_to_move(game, action), Some(qmove));
+        }
+    }
+
+    #[test]
+    fn actions_round_trip_for_both_players() {
+        let mut game = StandardQuoridor::initial_server();
+        assert_round_trips(&game);
+        let forward = Move::MovePawn((4, 0).into(), (4, 1).into());
+        assert_eq!(move_to_action(&game, forward), Some(9 + 4));
+
+        StandardQuoridor::apply_move(&mut game, forward);
+        assert_eq!(game.turn_of, 1);
+        assert_round_trips(&game);
+        // Player 1's step towards its goal is the same action as player 0's
+        let forward = Move::MovePawn((4, 8).into(), (4, 7).into());
+        assert_eq!(move_to_action(&game, forward), Some(9 + 4));
+
+        let wall = Wall::horizontal((3, 2).into());
+        let flipped = Wall::horizontal((3, 7).into());
+        assert_eq!(
+            move_to_action(&game, Move::PlaceWall(wall)),
+            move_to_action(
+                &StandardQuoridor::initial_server(),
+                Move::PlaceWall(flipped)
+            )
+        );
+    }
+
+    #[test]
+    fn mask_matches_legal_moves() {
+        let mut game = StandardQuoridor::initial_server();
+        StandardQuoridor::apply_move(&mut game, Move::PlaceWall(Wall::horizontal((4, 1).into())));
+        let mask = action_mask(&game);
+        assert_eq!(mask.len(), action_count::<StandardQuoridor>());
+        assert_eq!(
+            mask.iter().filter(|&&legal| legal).count(),
+            legal_moves(&game).len()
+        );
+        for (action, _) in mask.iter().enumerate().filter(|(_, &legal)| legal) {
+            let qmove = action_to_move(&game, action).unwrap();
+            assert!(StandardQuoridor::validate_move(&game, qmove).is_ok());
+        }
+    }
+
+    #[test]
+    fn toroidal_walls_have_seam_slots() {
+        assert_eq!(action_count::<ToroidalQuoridor>(), 81 + 2 * 9 * 8);
+        let mut game = ToroidalQuoridor::initial_server();
+        let seam = Move::PlaceWall(Wall::vertical((0, 4).into()));
+        let action = move_to_action(&game, seam).unwrap();
+        assert!(action < action_count::<ToroidalQuoridor>());
+        assert_eq!(action_to_move(&game, action), Some(seam));
+
+        ToroidalQuoridor::apply_move(&mut game, Move::MovePawn((4, 0).into(), (4, 1).into()));
+        assert_round_trips(&game);
+    }
+
+    #[test]
+    fn walls_without_a_slot_are_left_out() {
+        let mut game = FreeQuoridor::initial_server();
+        let edges = [
+            Wall::horizontal((0, 3).into()),
+            Wall::vertical((3, 0).into()),
+            Wall::vertical((3, 200).into()),
+        ];
+        for &wall in edges.iter() {
+            assert_eq!(move_to_action(&game, Move::PlaceWall(wall)), None);
+            game.walls.insert(wall);
+        }
+        let observation = observe(&game, &game.wall_counts);
+        let plane = 9 * 9;
+        assert!(observation[2 * plane..4 * plane].iter().all(|&v| v == 0.0));
+
+        game.turn_of = 1;
+        for &wall in edges.iter() {
+            assert_eq!(move_to_action(&game, Move::PlaceWall(wall)), None);
+        }
+        observe(&game, &game.wall_counts);
+    }
+
+    #[test]
+    fn finished_environments_are_reset() {
+        let mut envs = VecEnv::<StandardQuoridor>::new(2, 1).unwrap();
+        let fresh = envs.reset();
+        let actions = envs
+            .envs
+            .iter()
+            .map(|env| move_to_action(env.game(), legal_moves(env.game())[0]).unwrap())
+            .collect::<Vec<_>>();
+
+        let step = envs.step(&actions).unwrap();
+        assert_eq!(step.dones, vec![true, true]);
+        assert_eq!(step.truncated, vec![true, true]);
+        assert_eq!(step.observations, fresh);
+        assert!(envs
+            .envs
+            .iter()
+            .all(|env| !env.done() && env.game().turn_of == 0));
+        assert_eq!(step.masks, envs.action_masks());
+    }
+}
@@ -1,6 +1,8 @@
 use bimap::BiMap;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::collections::hash_set::HashSet;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{hash_map::HashMap, hash_set::HashSet};
 use tbmp_core::*;
 
 #[derive(Copy, Clone, Hash, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -53,7 +55,7 @@ pub enum Orientation {
     Horizontal,
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Hash, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Move {
     PlaceWall(Wall),
     RemoveWall(Wall),
@@ -66,6 +68,7 @@ pub type PawnID = u8;
 pub trait QGTrait: Send + Sync {
     fn pawns(&self) -> &BiMap<PawnID, Position>;
     fn walls(&self) -> &HashSet<Wall>;
+    fn wall_owners(&self) -> &HashMap<Wall, PlayerID>;
     fn turn_of(&self) -> PlayerID;
     fn wall_counts(&self) -> std::slice::Iter<u8>;
 }
@@ -79,6 +82,10 @@ impl<Rb: Rulebook> QGTrait for QGame<Rb> {
         &self.walls
     }
 
+    fn wall_owners(&self) -> &HashMap<Wall, PlayerID> {
+        &self.wall_owners
+    }
+
     fn turn_of(&self) -> u8 {
         self.turn_of
     }
@@ -93,6 +100,7 @@ pub struct QGame<Rb: Rulebook> {
     pub wall_counts: Vec<u8>, //Change this to [u8; Rb::PLAYER_COUNT] when compiler allows
     pub pawn_positions: BiMap<PawnID, Position>,
     pub walls: HashSet<Wall>,
+    pub wall_owners: HashMap<Wall, PlayerID>,
     pub turn_of: PlayerID,
     #[serde(bound = "")]
     pub metadata: Rb::Metadata,
@@ -101,15 +109,38 @@ pub struct QGame<Rb: Rulebook> {
 pub trait Rulebook: Send + Clone + 'static {
     const PLAYER_COUNT: u8;
     const PAWN_COUNT: u8;
+    const BOARD_SIZE: u8 = 9;
+    const TOROIDAL: bool = false;
+    // Whether players may pick up and move the walls they placed
+    const MOVABLE_WALLS: bool = false;
+    const HIDDEN_INFORMATION: bool = false;
 
     type Move: MoveTrait;
-    type Metadata: Serialize + DeserializeOwned + Send + Sync + Clone;
+    type Metadata: Serialize + DeserializeOwned + Send + Sync + Clone + 'static;
 
     fn validate_move(game: &QGame<Self>, qmove: Self::Move) -> Result<(), ()>;
 
     fn apply_move(game: &mut QGame<Self>, qmove: Self::Move) -> MoveResult;
 
     fn initial_server() -> QGame<Self>;
+
+    fn redact(game: &QGame<Self>, _player: PlayerID) -> QGame<Self> {
+        Clone::clone(game)
+    }
+
+    // Rule options picked when a game is set up, like the seed of a random setup
+    fn set_option(_game: &mut QGame<Self>, _key: &str, _value: &str) -> Result<(), ()> {
+        Err(())
+    }
+}
+
+thread_local! {
+    // tbmp asks the game type for its start, so a start chosen at run time waits here for the next game
+    static NEXT_START: RefCell<Option<Box<dyn Any>>> = RefCell::new(None);
+}
+
+pub(crate) fn start_next_game_from<Rb: Rulebook>(start: Option<QGame<Rb>>) {
+    NEXT_START.with(|next| *next.borrow_mut() = start.map(|start| Box::new(start) as Box<dyn Any>));
 }
 
 impl<Rb: Rulebook> Game for QGame<Rb> {
@@ -126,7 +157,10 @@ impl<Rb: Rulebook> Game for QGame<Rb> {
     }
 
     fn initial_server() -> Self {
-        Rb::initial_server()
+        NEXT_START
+            .with(|next| next.borrow_mut().take())
+            .and_then(|start| start.downcast::<Self>().ok())
+            .map_or_else(Rb::initial_server, |start| *start)
     }
 
     fn turn_of(&self) -> u8 {
@@ -137,4 +171,14 @@ impl<Rb: Rulebook> Game for QGame<Rb> {
 pub trait MoveTrait: Copy + Send + Serialize + DeserializeOwned {}
 impl MoveTrait for Move {}
 
+pub mod ai;
+pub mod archive;
+pub mod engine;
+pub mod env;
+pub mod net;
+pub mod notation;
+pub mod record;
+pub mod redaction;
+pub mod rng;
 pub mod rulebooks;
+pub mod solver;
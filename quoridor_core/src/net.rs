@@ -0,0 +1,1120 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::error::Error;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tbmp_core::*;
+
+use crate::{notation, Move, QGame, Rulebook};
+
+// TCP transport for agents. Each message is bincode, preceded by its length as a little-endian u32.
+// A client opens with a MatchRequest and is answered with a MatchReply; after that hosts send
+// GameEvents and clients send moves. An empty frame is a heartbeat, which clients echo back.
+//
+// Hosts also speak a line-based text protocol, for terminals and simple scripts. A connection
+// uses it if its first line is one of
+//     play [RULEBOOK] [KEY=VALUE]...    (code=CODE asks for a private game)
+//     watch GAME
+//     resume TOKEN
+// which is answered with "queued [TOKEN]", "watching" or "rejected REASON". After that the host
+// sends one event per line,
+//     start SEAT POSITION | move MOVE | turn | ok | invalid | quit | end WINNER|draw | error MESSAGE
+// and the client sends moves in notation, one per line. SEAT is "spectator" for spectators.
+
+const MAX_FRAME_LEN: usize = 1 << 20;
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MatchRequest {
+    // The rulebook's type name, e.g. StandardQuoridor; empty leaves the choice to the server
+    pub rulebook: String,
+    // Players are only paired with others asking for the same options
+    pub options: Vec<(String, String)>,
+    // Players giving the same code are paired with each other and nobody else
+    pub code: Option<String>,
+    // Asks to spectate a running game, named by its number or private code, instead of playing
+    pub watch: Option<String>,
+    // A session token from an earlier connection, to take that seat back in a running game
+    pub resume: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Binary,
+    Text,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum MatchReply {
+    // Carries a session token when the host holds seats for players who drop out
+    Queued(Option<String>),
+    Watching,
+    Rejected(String),
+}
+
+// The seat spectators are told they have in GameStart; it is never anyone's turn
+pub const SPECTATOR: PlayerID = PlayerID::MAX;
+// How far a spectator may fall behind, in bytes waiting to be sent, before being dropped
+const SPECTATOR_BACKLOG: usize = 64 * 1024;
+// How long a dropped player's seat is held, and how often their client tries to take it back
+pub const RECONNECT_GRACE: Duration = Duration::from_secs(60);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+// A binary connection that says nothing for this many heartbeats is taken as dropped
+const MISSED_HEARTBEATS: u32 = 3;
+
+// How long a hosted player may be out of reach, or sit on a move, before forfeiting
+#[derive(Clone, Copy, Debug)]
+pub struct Timeouts {
+    // How long a dropped player's seat is held for them to reconnect
+    pub grace: Duration,
+    // Only binary connections get heartbeats, since text clients are often people at a terminal
+    pub heartbeat: Option<Duration>,
+    pub move_time: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            grace: RECONNECT_GRACE,
+            heartbeat: Some(HEARTBEAT_INTERVAL),
+            move_time: None,
+        }
+    }
+}
+
+// Sockets are non-blocking, so a full send buffer is waited out rather than treated as an error
+fn write_all(stream: &mut dyn Write, mut bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    while !bytes.is_empty() {
+        match stream.write(bytes) {
+            Ok(0) => return Err("connection closed".into()),
+            Ok(n) => bytes = &bytes[n..],
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(1))
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+fn frame<T: Serialize>(value: &T) -> Result<Vec<u8>, Box<dyn Error>> {
+    let bytes = bincode::serialize(value)?;
+    let mut frame = (bytes.len() as u32).to_le_bytes().to_vec();
+    frame.extend(bytes);
+    Ok(frame)
+}
+
+pub fn write_frame<T: Serialize>(stream: &mut dyn Write, value: &T) -> Result<(), Box<dyn Error>> {
+    write_all(stream, &frame(value)?)?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn write_heartbeat(stream: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    write_all(stream, &0u32.to_le_bytes())?;
+    stream.flush()?;
+    Ok(())
+}
+
+pub struct FrameReader {
+    buffer: Vec<u8>,
+    closed: bool,
+    heartbeats: usize,
+    heard: Instant,
+}
+
+impl Default for FrameReader {
+    fn default() -> Self {
+        FrameReader {
+            buffer: vec![],
+            closed: false,
+            heartbeats: 0,
+            heard: Instant::now(),
+        }
+    }
+}
+
+impl FrameReader {
+    // Takes whatever a non-blocking stream has available
+    fn fill(&mut self, stream: &mut dyn Read) -> Result<(), Box<dyn Error>> {
+        let mut chunk = [0u8; 4096];
+        while !self.closed {
+            match stream.read(&mut chunk) {
+                Ok(0) => self.closed = true,
+                Ok(n) => {
+                    self.buffer.extend_from_slice(&chunk[..n]);
+                    self.heard = Instant::now();
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    // How long since the other end last sent anything
+    pub fn silence(&self) -> Duration {
+        self.heard.elapsed()
+    }
+
+    // The heartbeats received since the last call
+    pub fn take_heartbeats(&mut self) -> usize {
+        std::mem::replace(&mut self.heartbeats, 0)
+    }
+
+    // Returns the next complete message, if any
+    pub fn poll<T: DeserializeOwned>(
+        &mut self,
+        stream: &mut dyn Read,
+    ) -> Result<Option<T>, Box<dyn Error>> {
+        self.fill(stream)?;
+        match self.next_frame()? {
+            Some(value) => Ok(Some(value)),
+            None if self.closed => Err("connection closed".into()),
+            None => Ok(None),
+        }
+    }
+
+    // Returns the next complete line of a text connection, without its line ending
+    pub fn poll_line(&mut self, stream: &mut dyn Read) -> Result<Option<String>, Box<dyn Error>> {
+        self.fill(stream)?;
+        match self.buffer.iter().position(|&b| b == b'\n') {
+            Some(end) => {
+                let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+                let line = String::from_utf8(line).map_err(|_| "not text")?;
+                Ok(Some(line.trim_end().into()))
+            }
+            None if self.buffer.len() > MAX_FRAME_LEN => Err("line too long".into()),
+            None if self.closed => Err("connection closed".into()),
+            None => Ok(None),
+        }
+    }
+
+    // Reads a client's opening request in either protocol. Binary frames are shorter than 16 MiB,
+    // so their fourth byte is zero, which no line of text starts with. A text client whose request
+    // makes no sense is told so before the error is returned.
+    pub fn poll_request<S: Read + Write>(
+        &mut self,
+        stream: &mut S,
+    ) -> Result<Option<(MatchRequest, Protocol)>, Box<dyn Error>> {
+        self.fill(stream)?;
+        match self.buffer.get(3) {
+            Some(0) => Ok(self.poll(stream)?.map(|request| (request, Protocol::Binary))),
+            Some(_) => match self.poll_line(stream)? {
+                Some(line) => match parse_request_line(&line) {
+                    Ok(request) => Ok(Some((request, Protocol::Text))),
+                    Err(e) => {
+                        write_line(stream, &format!("rejected {}", e)).ok();
+                        Err(e)
+                    }
+                },
+                None => Ok(None),
+            },
+            None if self.closed => Err("connection closed".into()),
+            None => Ok(None),
+        }
+    }
+
+    fn next_frame<T: DeserializeOwned>(&mut self) -> Result<Option<T>, Box<dyn Error>> {
+        while self.buffer.len() >= 4 && self.buffer[..4] == [0; 4] {
+            self.buffer.drain(..4);
+            self.heartbeats += 1;
+        }
+        if self.buffer.len() < 4 {
+            return Ok(None);
+        }
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&self.buffer[..4]);
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err("frame too large".into());
+        }
+        if self.buffer.len() < 4 + len {
+            return Ok(None);
+        }
+        let value = bincode::deserialize(&self.buffer[4..4 + len])?;
+        self.buffer.drain(..4 + len);
+        Ok(Some(value))
+    }
+}
+
+fn parse_request_line(line: &str) -> Result<MatchRequest, Box<dyn Error>> {
+    let mut words = line.split_whitespace();
+    let mut request = MatchRequest::default();
+    match (words.next(), words.next(), words.clone().next()) {
+        (Some("play"), rulebook, _) => {
+            for word in rulebook.into_iter().chain(words) {
+                let mut parts = word.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some("code"), Some(code)) => request.code = Some(code.into()),
+                    (Some(key), Some(value)) => request.options.push((key.into(), value.into())),
+                    (Some(rulebook), None) if request.rulebook.is_empty() => request.rulebook = rulebook.into(),
+                    _ => return Err(format!("unexpected {}", word).into()),
+                }
+            }
+        }
+        (Some("watch"), Some(game), None) => request.watch = Some(game.into()),
+        (Some("resume"), Some(token), None) => request.resume = Some(token.into()),
+        _ => return Err("expected play, watch or resume".into()),
+    }
+    Ok(request)
+}
+
+fn reply_line(reply: &MatchReply) -> String {
+    match reply {
+        MatchReply::Queued(Some(token)) => format!("queued {}", token),
+        MatchReply::Queued(None) => "queued".into(),
+        MatchReply::Watching => "watching".into(),
+        MatchReply::Rejected(reason) => format!("rejected {}", reason),
+    }
+}
+
+pub fn write_reply(
+    stream: &mut dyn Write,
+    protocol: Protocol,
+    reply: &MatchReply,
+) -> Result<(), Box<dyn Error>> {
+    match protocol {
+        Protocol::Binary => write_frame(stream, reply),
+        Protocol::Text => write_line(stream, &reply_line(reply)),
+    }
+}
+
+fn write_line(stream: &mut dyn Write, line: &str) -> Result<(), Box<dyn Error>> {
+    write_all(stream, format!("{}\n", line).as_bytes())?;
+    stream.flush()?;
+    Ok(())
+}
+
+// Games that can be played over the text protocol
+pub trait TextGame: Game {
+    fn move_to_text(qmove: Self::Move) -> Result<String, ()>;
+    fn move_from_text(&self, text: &str) -> Result<Self::Move, ()>;
+    fn position_to_text(&self) -> Result<String, ()>;
+}
+
+impl<Rb: Rulebook<Move = Move>> TextGame for QGame<Rb> {
+    fn move_to_text(qmove: Move) -> Result<String, ()> {
+        notation::move_to_string::<Rb>(qmove)
+    }
+
+    fn move_from_text(&self, text: &str) -> Result<Move, ()> {
+        notation::parse_move(self, text)
+    }
+
+    fn position_to_text(&self) -> Result<String, ()> {
+        notation::position_to_string(self)
+    }
+}
+
+fn event_line<G: TextGame>(event: &GameEvent<G>) -> Result<String, ()>
+where
+    G::Move: Copy,
+{
+    Ok(match event {
+        GameEvent::GameStart(game, SPECTATOR) => format!("start spectator {}", game.position_to_text()?),
+        GameEvent::GameStart(game, seat) => format!("start {} {}", seat, game.position_to_text()?),
+        GameEvent::MoveHappened(qmove) => format!("move {}", G::move_to_text(*qmove)?),
+        GameEvent::YourTurn => "turn".into(),
+        GameEvent::ValidMove => "ok".into(),
+        GameEvent::InvalidMove => "invalid".into(),
+        GameEvent::OpponentQuit => "quit".into(),
+        GameEvent::GameEnd(Some(winner)) => format!("end {}", winner),
+        GameEvent::GameEnd(None) => "end draw".into(),
+    })
+}
+
+fn encode_event<G: TextGame>(protocol: Protocol, event: &GameEvent<G>) -> Result<Vec<u8>, Box<dyn Error>>
+where
+    G::Move: Copy,
+{
+    match protocol {
+        Protocol::Binary => frame(event),
+        Protocol::Text => {
+            let line = event_line(event).map_err(|_| "event has no text notation")?;
+            Ok(format!("{}\n", line).into_bytes())
+        }
+    }
+}
+
+fn write_event<G: TextGame>(
+    stream: &mut dyn Write,
+    protocol: Protocol,
+    event: &GameEvent<G>,
+) -> Result<(), Box<dyn Error>>
+where
+    G::Move: Copy,
+{
+    write_all(stream, &encode_event(protocol, event)?)?;
+    stream.flush()?;
+    Ok(())
+}
+
+// Reads the moves a seated player has sent. Text that is not a move is answered with an error
+// line rather than passed on, since the game could not tell the player what was wrong with it.
+fn read_moves<G: TextGame>(
+    stream: &mut TcpStream,
+    protocol: Protocol,
+    reader: &mut FrameReader,
+    game: Option<&G>,
+) -> Result<Vec<G::Move>, Box<dyn Error>> {
+    let mut moves = vec![];
+    match protocol {
+        Protocol::Binary => {
+            while let Some(qmove) = reader.poll::<G::Move>(stream)? {
+                moves.push(qmove);
+            }
+        }
+        Protocol::Text => {
+            while let Some(line) = reader.poll_line(stream)? {
+                if line.is_empty() {
+                    continue;
+                }
+                match game.map(|game| game.move_from_text(&line)) {
+                    Some(Ok(qmove)) => moves.push(qmove),
+                    _ => write_line(stream, &format!("error cannot read move {}", line))?,
+                }
+            }
+        }
+    }
+    Ok(moves)
+}
+
+// Relays a seated player's events to the socket and the moves it sends back into the game
+pub fn serve_agent<G: TextGame>(
+    mut stream: TcpStream,
+    core: AgentCore<G>,
+    protocol: Protocol,
+) -> Result<impl Send + Sync + FnMut() -> Result<(), Box<dyn Error>>, Box<dyn Error>>
+where
+    G::Move: Copy,
+{
+    stream.set_nonblocking(true)?;
+    stream.set_nodelay(true)?;
+    let mut reader = FrameReader::default();
+    let mut follower = Follower::default();
+
+    Ok(move || {
+        while let Ok(event) = core.event_channel.try_recv() {
+            follower.follow(&event);
+            write_event(&mut stream, protocol, &event)?;
+        }
+        for qmove in read_moves(&mut stream, protocol, &mut reader, follower.game())? {
+            core.move_channel.send(qmove).map_err(|_| "game closed")?;
+        }
+        Ok(())
+    })
+}
+
+// Keeps a copy of a game from the events one seat receives, to bring late or returning
+// connections up to date
+pub struct Follower<G: Game> {
+    game: Option<G>,
+    seat: PlayerID,
+    winner: Option<Option<PlayerID>>,
+}
+
+impl<G: Game> Default for Follower<G> {
+    fn default() -> Self {
+        Follower {
+            game: None,
+            seat: 0,
+            winner: None,
+        }
+    }
+}
+
+impl<G: Game> Follower<G>
+where
+    G::Move: Copy,
+{
+    pub fn follow(&mut self, event: &GameEvent<G>) {
+        match event {
+            GameEvent::GameStart(game, seat) => {
+                self.game = Some(game.clone());
+                self.seat = *seat;
+            }
+            GameEvent::MoveHappened(qmove) => {
+                if let Some(game) = &mut self.game {
+                    game.apply_move(*qmove);
+                }
+            }
+            GameEvent::GameEnd(winner) => self.winner = Some(*winner),
+            _ => {}
+        }
+    }
+
+    pub fn seat(&self) -> PlayerID {
+        self.seat
+    }
+
+    pub fn game(&self) -> Option<&G> {
+        self.game.as_ref()
+    }
+
+    // Whether the game is waiting on this seat
+    pub fn to_move(&self) -> bool {
+        self.winner.is_none() && self.game.as_ref().map_or(false, |game| game.turn_of() == self.seat)
+    }
+
+    // The events that take a fresh connection sitting in `seat` to the position as it stands
+    pub fn catch_up(&self, seat: PlayerID) -> Vec<GameEvent<G>> {
+        let mut events = vec![];
+        if let Some(game) = &self.game {
+            events.push(GameEvent::GameStart(game.clone(), seat));
+        }
+        if let Some(winner) = self.winner {
+            events.push(GameEvent::GameEnd(winner));
+        }
+        events
+    }
+}
+
+pub trait Audience: Send {
+    fn join(&mut self, stream: TcpStream, protocol: Protocol) -> Result<(), Box<dyn Error>>;
+    // Announces a result the game itself did not reach, such as a forfeit
+    fn conclude(&mut self, winner: Option<PlayerID>);
+}
+
+struct Spectator {
+    stream: TcpStream,
+    protocol: Protocol,
+    // Bytes the socket has not taken yet
+    queue: Vec<u8>,
+}
+
+impl Spectator {
+    // Queues `bytes` and sends what the socket takes without waiting;
+    // None once the connection is gone or too far behind
+    fn send(mut self, bytes: &[u8]) -> Option<Self> {
+        self.queue.extend_from_slice(bytes);
+        while !self.queue.is_empty() {
+            match self.stream.write(&self.queue) {
+                Ok(0) => return None,
+                Ok(n) => {
+                    self.queue.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return None,
+            }
+        }
+        Some(self).filter(|spectator| spectator.queue.len() <= SPECTATOR_BACKLOG)
+    }
+}
+
+// Read-only connections following a game. Anything they send is ignored. Writes are queued per
+// spectator so the game never waits on one, and one that falls too far behind is dropped.
+pub struct Spectators<G: Game> {
+    follower: Follower<G>,
+    spectators: Vec<Spectator>,
+}
+
+impl<G: TextGame> Spectators<G>
+where
+    G::Move: Copy,
+{
+    fn broadcast(&mut self, event: &GameEvent<G>) {
+        let binary = encode_event(Protocol::Binary, event).unwrap_or_default();
+        let text = encode_event(Protocol::Text, event).unwrap_or_default();
+        self.spectators = std::mem::take(&mut self.spectators)
+            .into_iter()
+            .filter_map(|spectator| match spectator.protocol {
+                Protocol::Binary => spectator.send(&binary),
+                Protocol::Text => spectator.send(&text),
+            })
+            .collect();
+    }
+
+    // Keeps the queues draining between events
+    fn flush(&mut self) {
+        self.spectators = std::mem::take(&mut self.spectators)
+            .into_iter()
+            .filter_map(|spectator| spectator.send(&[]))
+            .collect();
+    }
+
+    fn observe(&mut self, event: &GameEvent<G>) {
+        self.follower.follow(event);
+        match event {
+            GameEvent::GameStart(game, _) => {
+                self.broadcast(&GameEvent::GameStart(game.clone(), SPECTATOR))
+            }
+            GameEvent::MoveHappened(qmove) => self.broadcast(&GameEvent::MoveHappened(*qmove)),
+            GameEvent::GameEnd(winner) => self.broadcast(&GameEvent::GameEnd(*winner)),
+            _ => {}
+        }
+    }
+}
+
+impl<G: TextGame> Audience for Spectators<G>
+where
+    G::Move: Copy,
+{
+    fn join(&mut self, stream: TcpStream, protocol: Protocol) -> Result<(), Box<dyn Error>> {
+        stream.set_nonblocking(true)?;
+        let mut bytes = vec![];
+        for event in self.follower.catch_up(SPECTATOR) {
+            bytes.extend(encode_event(protocol, &event)?);
+        }
+        let spectator = Spectator {
+            stream,
+            protocol,
+            queue: vec![],
+        };
+        self.spectators.push(spectator.send(&bytes).ok_or("spectator gone")?);
+        Ok(())
+    }
+
+    fn conclude(&mut self, winner: Option<PlayerID>) {
+        self.observe(&GameEvent::GameEnd(winner));
+    }
+}
+
+// Anyone holding a token can take the seat back, so tokens come from the OS rather than a seeded generator
+#[cfg(not(target_arch = "wasm32"))]
+pub fn session_token() -> Result<String, Box<dyn Error>> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+pub trait Session: Send {
+    fn resume(&mut self, stream: TcpStream, protocol: Protocol) -> Result<(), Box<dyn Error>>;
+    // Ends the game for this seat because the player in seat `by` forfeited
+    fn forfeit(&mut self, by: PlayerID, winner: Option<PlayerID>);
+}
+
+// A player's seat in a hosted game. If the connection drops, the seat is held for the grace period
+// so the player can come back with their session token and carry on where they left off.
+pub struct Seat<G: Game> {
+    core: AgentCore<G>,
+    follower: Follower<G>,
+    stream: Option<TcpStream>,
+    protocol: Protocol,
+    reader: FrameReader,
+    dropped: Option<Instant>,
+    timeouts: Timeouts,
+    last_heartbeat: Instant,
+    // When the game started waiting on this seat's move
+    turn_since: Option<Instant>,
+}
+
+impl<G: TextGame> Seat<G>
+where
+    G::Move: Copy,
+{
+    // Fails once the player has been gone longer than the grace period or has run out of time
+    fn pump(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut lost = false;
+        while let Ok(event) = self.core.event_channel.try_recv() {
+            self.follower.follow(&event);
+            if let GameEvent::MoveHappened(_) = event {
+                self.turn_since = None;
+            }
+            if let Some(stream) = &mut self.stream {
+                lost |= write_event(stream, self.protocol, &event).is_err();
+            }
+        }
+        if !self.follower.to_move() {
+            self.turn_since = None;
+        } else if self.turn_since.is_none() {
+            self.turn_since = Some(Instant::now());
+        }
+
+        if let (Some(stream), Protocol::Binary, Some(interval)) =
+            (&mut self.stream, self.protocol, self.timeouts.heartbeat)
+        {
+            if self.last_heartbeat.elapsed() >= interval {
+                self.last_heartbeat = Instant::now();
+                lost |= write_heartbeat(stream).is_err();
+            }
+            lost |= self.reader.silence() > interval * MISSED_HEARTBEATS;
+        }
+        if let Some(stream) = &mut self.stream {
+            match read_moves(stream, self.protocol, &mut self.reader, self.follower.game()) {
+                Ok(moves) => {
+                    for qmove in moves {
+                        self.core.move_channel.send(qmove).map_err(|_| "game closed")?;
+                    }
+                }
+                Err(_) => lost = true,
+            }
+        }
+
+        if lost && self.stream.is_some() {
+            self.stream = None;
+            self.dropped = Some(Instant::now());
+        }
+        match (self.dropped, self.turn_since, self.timeouts.move_time) {
+            (Some(since), _, _) if since.elapsed() > self.timeouts.grace => Err("connection lost".into()),
+            (_, Some(since), Some(limit)) if since.elapsed() > limit => Err("out of time".into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<G: TextGame> Session for Seat<G>
+where
+    G::Move: Copy,
+{
+    // Replaces the seat's connection, even one that still looks alive, and resends the position
+    fn resume(&mut self, mut stream: TcpStream, protocol: Protocol) -> Result<(), Box<dyn Error>> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        for event in self.follower.catch_up(self.follower.seat()) {
+            write_event(&mut stream, protocol, &event)?;
+        }
+        self.stream = Some(stream);
+        self.protocol = protocol;
+        self.reader = FrameReader::default();
+        self.dropped = None;
+        self.last_heartbeat = Instant::now();
+        Ok(())
+    }
+
+    fn forfeit(&mut self, by: PlayerID, winner: Option<PlayerID>) {
+        let mut events = vec![];
+        if by != self.follower.seat() {
+            events.push(GameEvent::OpponentQuit);
+        }
+        events.push(GameEvent::GameEnd(winner));
+        for event in events {
+            self.follower.follow(&event);
+            if let Some(stream) = &mut self.stream {
+                write_event(stream, self.protocol, &event).ok();
+            }
+        }
+    }
+}
+
+// Like serve_agent, but the player can reconnect after losing their connection, and the seat
+// fails when they stay away or take too long over a move
+pub fn seat<G: TextGame>(
+    stream: TcpStream,
+    protocol: Protocol,
+    core: AgentCore<G>,
+    timeouts: Timeouts,
+) -> Result<
+    (
+        Arc<Mutex<Seat<G>>>,
+        impl Send + Sync + FnMut() -> Result<(), Box<dyn Error>>,
+    ),
+    Box<dyn Error>,
+>
+where
+    G::Move: Copy,
+{
+    stream.set_nonblocking(true)?;
+    stream.set_nodelay(true)?;
+    let seat = Arc::new(Mutex::new(Seat {
+        core,
+        follower: Follower::default(),
+        stream: Some(stream),
+        protocol,
+        reader: FrameReader::default(),
+        dropped: None,
+        timeouts,
+        last_heartbeat: Instant::now(),
+        turn_since: None,
+    }));
+    let handle = seat.clone();
+    Ok((seat, move || handle.lock().map_err(|_| "seat poisoned")?.pump()))
+}
+
+// Passes a seated player's events through unchanged, following the game for spectators on the way
+pub fn tap<G: TextGame>(
+    core: AgentCore<G>,
+) -> (
+    AgentCore<G>,
+    Arc<Mutex<Spectators<G>>>,
+    impl Send + Sync + FnMut() -> Result<(), Box<dyn Error>>,
+)
+where
+    G::Move: Copy,
+{
+    let spectators = Arc::new(Mutex::new(Spectators {
+        follower: Follower::default(),
+        spectators: vec![],
+    }));
+    let AgentCore {
+        event_channel: upstream,
+        move_channel,
+    } = core;
+    let (events, event_channel) = crossbeam_channel::unbounded();
+    let audience = spectators.clone();
+    let relay = move || {
+        while let Ok(event) = upstream.try_recv() {
+            if let Ok(mut spectators) = spectators.lock() {
+                spectators.observe(&event);
+            }
+            events.send(event).map_err(|_| "agent closed")?;
+        }
+        if let Ok(mut spectators) = spectators.lock() {
+            spectators.flush();
+        }
+        Ok(())
+    };
+    (
+        AgentCore {
+            event_channel,
+            move_channel,
+        },
+        audience,
+        relay,
+    )
+}
+
+// Lets spectators join a game whose seats are all taken, turning away anyone else;
+// without an audience, as for games with hidden information, spectators are turned away too
+pub fn spectator_desk(
+    listener: &TcpListener,
+    audience: Option<Arc<Mutex<dyn Audience>>>,
+) -> Result<impl Send + Sync + FnMut() -> Result<(), Box<dyn Error>>, Box<dyn Error>> {
+    let listener = listener.try_clone()?;
+    listener.set_nonblocking(true)?;
+    let mut pending: Vec<(TcpStream, FrameReader, Instant)> = vec![];
+
+    Ok(move || {
+        while let Ok((stream, _)) = listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                pending.push((stream, FrameReader::default(), Instant::now()));
+            }
+        }
+        for (mut stream, mut reader, since) in pending.drain(..).collect::<Vec<_>>() {
+            match reader.poll_request(&mut stream) {
+                Ok(Some((request, protocol))) if request.watch.is_some() => match &audience {
+                    Some(audience) => {
+                        if write_reply(&mut stream, protocol, &MatchReply::Watching).is_ok() {
+                            if let Ok(mut audience) = audience.lock() {
+                                audience.join(stream, protocol).ok();
+                            }
+                        }
+                    }
+                    None => {
+                        let reply = MatchReply::Rejected("this game can't be watched".into());
+                        write_reply(&mut stream, protocol, &reply).ok();
+                    }
+                },
+                Ok(Some((_, protocol))) => {
+                    let reply = MatchReply::Rejected("this game is full".into());
+                    write_reply(&mut stream, protocol, &reply).ok();
+                }
+                Ok(None) if since.elapsed() < HANDSHAKE_TIMEOUT => pending.push((stream, reader, since)),
+                _ => {}
+            }
+        }
+        Ok(())
+    })
+}
+
+// Waits for a freshly accepted client to send its MatchRequest; the stream is left non-blocking
+pub fn read_request(stream: &mut TcpStream) -> Result<(MatchRequest, Protocol), Box<dyn Error>> {
+    stream.set_nonblocking(true)?;
+    let mut reader = FrameReader::default();
+    let start = Instant::now();
+    loop {
+        if let Some(request) = reader.poll_request(stream)? {
+            return Ok(request);
+        }
+        if start.elapsed() > HANDSHAKE_TIMEOUT {
+            return Err("no match request".into());
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+// Opens a connection and waits for the host to answer the request
+fn handshake(
+    addr: SocketAddr,
+    request: &MatchRequest,
+) -> Result<(TcpStream, FrameReader, MatchReply), Box<dyn Error>> {
+    let mut stream = TcpStream::connect_timeout(&addr, HANDSHAKE_TIMEOUT)?;
+    stream.set_nonblocking(true)?;
+    stream.set_nodelay(true)?;
+
+    let mut reader = FrameReader::default();
+    write_frame(&mut stream, request)?;
+    let start = Instant::now();
+    loop {
+        match reader.poll(&mut stream)? {
+            Some(reply) => return Ok((stream, reader, reply)),
+            None if start.elapsed() > HANDSHAKE_TIMEOUT => return Err("no reply from server".into()),
+            None => std::thread::sleep(Duration::from_millis(10)),
+        }
+    }
+}
+
+fn relay<G: Game>(
+    stream: &mut TcpStream,
+    reader: &mut FrameReader,
+    events: &crossbeam_channel::Sender<GameEvent<G>>,
+    moves: &crossbeam_channel::Receiver<G::Move>,
+    over: &mut bool,
+) -> Result<(), Box<dyn Error>> {
+    while let Some(event) = reader.poll::<GameEvent<G>>(stream)? {
+        *over |= matches!(event, GameEvent::GameEnd(_));
+        events.send(event).map_err(|_| "agent closed")?;
+    }
+    for _ in 0..reader.take_heartbeats() {
+        write_heartbeat(stream)?;
+    }
+    while let Ok(qmove) = moves.try_recv() {
+        write_frame(stream, &qmove)?;
+    }
+    Ok(())
+}
+
+// Joins a game on a host. If the host hands out a session token, a dropped connection is
+// re-established in the background and the agent gets a fresh GameStart once it is back.
+pub fn connect<G: Game>(
+    addr: SocketAddr,
+    request: &MatchRequest,
+) -> Result<
+    (
+        AgentCore<G>,
+        impl Send + Sync + FnMut() -> Result<(), Box<dyn Error>>,
+    ),
+    Box<dyn Error>,
+> {
+    let (stream, reader, reply) = handshake(addr, request)?;
+    let session = match reply {
+        MatchReply::Queued(session) => session,
+        MatchReply::Watching => None,
+        MatchReply::Rejected(reason) => return Err(reason.into()),
+    };
+
+    let (events, event_channel) = crossbeam_channel::unbounded();
+    let (move_channel, moves) = crossbeam_channel::unbounded();
+    let mut link = Some((stream, reader));
+    let mut lost = Instant::now();
+    let mut last_attempt = Instant::now();
+    let mut over = false;
+
+    let agent = move || {
+        if let Some((stream, reader)) = &mut link {
+            match relay(stream, reader, &events, &moves, &mut over) {
+                Ok(()) => return Ok(()),
+                Err(e) if session.is_none() || over => return Err(e),
+                Err(_) => {
+                    link = None;
+                    lost = Instant::now();
+                }
+            }
+        }
+
+        if lost.elapsed() > RECONNECT_GRACE {
+            return Err("connection lost".into());
+        }
+        if last_attempt.elapsed() >= RECONNECT_INTERVAL {
+            last_attempt = Instant::now();
+            let request = MatchRequest {
+                resume: session.clone(),
+                ..MatchRequest::default()
+            };
+            match handshake(addr, &request) {
+                Ok((stream, reader, MatchReply::Queued(_))) => link = Some((stream, reader)),
+                Ok((_, _, MatchReply::Rejected(reason))) => return Err(reason.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    };
+    Ok((
+        AgentCore {
+            event_channel,
+            move_channel,
+        },
+        agent,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rulebooks::StandardQuoridor;
+    use std::collections::VecDeque;
+
+    // Stands in for a non-blocking socket: reads what has been fed so far, then would block
+    #[derive(Default)]
+    struct Pipe {
+        input: VecDeque<u8>,
+        output: Vec<u8>,
+        closed: bool,
+    }
+
+    impl Pipe {
+        fn feed(&mut self, bytes: &[u8]) {
+            self.input.extend(bytes);
+        }
+    }
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.input.is_empty() && !self.closed {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            let n = buf.len().min(self.input.len());
+            for (byte, b) in buf.iter_mut().zip(self.input.drain(..n)) {
+                *byte = b;
+            }
+            Ok(n)
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn request() -> MatchRequest {
+        MatchRequest {
+            rulebook: "StandardQuoridor".into(),
+            options: vec![("walls".into(), "8".into())],
+            ..MatchRequest::default()
+        }
+    }
+
+    #[test]
+    fn frames_arrive_in_pieces() {
+        let bytes = frame(&request()).unwrap();
+        let mut pipe = Pipe::default();
+        let mut reader = FrameReader::default();
+        for &byte in &bytes[..bytes.len() - 1] {
+            pipe.feed(&[byte]);
+            assert_eq!(reader.poll::<MatchRequest>(&mut pipe).unwrap(), None);
+        }
+        pipe.feed(&bytes[bytes.len() - 1..]);
+        assert_eq!(reader.poll(&mut pipe).unwrap(), Some(request()));
+    }
+
+    #[test]
+    fn empty_frames_are_heartbeats() {
+        let mut pipe = Pipe::default();
+        let mut reader = FrameReader::default();
+        pipe.feed(&[0; 8]);
+        pipe.feed(&frame(&request()).unwrap());
+        pipe.feed(&[0; 4]);
+        assert_eq!(reader.poll(&mut pipe).unwrap(), Some(request()));
+        assert_eq!(reader.poll::<MatchRequest>(&mut pipe).unwrap(), None);
+        assert_eq!(reader.take_heartbeats(), 3);
+        assert_eq!(reader.take_heartbeats(), 0);
+
+        write_heartbeat(&mut pipe).unwrap();
+        assert_eq!(pipe.output, vec![0; 4]);
+    }
+
+    #[test]
+    fn bad_frames_are_errors() {
+        let mut pipe = Pipe::default();
+        pipe.feed(&(MAX_FRAME_LEN as u32 + 1).to_le_bytes());
+        assert!(FrameReader::default()
+            .poll::<MatchRequest>(&mut pipe)
+            .is_err());
+
+        let mut pipe = Pipe::default();
+        pipe.feed(&frame(&request()).unwrap()[..6]);
+        pipe.closed = true;
+        assert!(FrameReader::default()
+            .poll::<MatchRequest>(&mut pipe)
+            .is_err());
+    }
+
+    #[test]
+    fn requests_pick_their_protocol() {
+        let mut pipe = Pipe::default();
+        pipe.feed(&frame(&request()).unwrap());
+        let reply = FrameReader::default().poll_request(&mut pipe).unwrap();
+        assert_eq!(reply, Some((request(), Protocol::Binary)));
+
+        let mut pipe = Pipe::default();
+        let mut reader = FrameReader::default();
+        pipe.feed(b"pla");
+        assert_eq!(reader.poll_request(&mut pipe).unwrap(), None);
+        pipe.feed(b"y StandardQuoridor walls=8\r\n");
+        let reply = reader.poll_request(&mut pipe).unwrap();
+        assert_eq!(reply, Some((request(), Protocol::Text)));
+    }
+
+    #[test]
+    fn text_requests_that_make_no_sense_are_rejected() {
+        let mut pipe = Pipe::default();
+        pipe.feed(b"dance\n");
+        assert!(FrameReader::default().poll_request(&mut pipe).is_err());
+        assert!(String::from_utf8(pipe.output)
+            .unwrap()
+            .starts_with("rejected "));
+
+        let mut pipe = Pipe::default();
+        pipe.feed(b"play \xff\n");
+        assert!(FrameReader::default().poll_request(&mut pipe).is_err());
+    }
+
+    #[test]
+    fn request_lines() {
+        let request = parse_request_line("play").unwrap();
+        assert_eq!(request, MatchRequest::default());
+
+        let request = parse_request_line("play walls=4 code=abc").unwrap();
+        assert_eq!(request.rulebook, "");
+        assert_eq!(request.options, vec![("walls".into(), "4".into())]);
+        assert_eq!(request.code, Some("abc".into()));
+
+        assert_eq!(
+            parse_request_line("watch 3").unwrap().watch,
+            Some("3".into())
+        );
+        assert_eq!(
+            parse_request_line("resume f00d").unwrap().resume,
+            Some("f00d".into())
+        );
+
+        for line in ["", "watch", "watch 3 4", "resume", "play a b", "quit"].iter() {
+            assert!(parse_request_line(line).is_err(), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn spectators_that_stop_reading_are_dropped() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut reading = TcpStream::connect(addr).unwrap();
+        let (first, _) = listener.accept().unwrap();
+        let _stalled = TcpStream::connect(addr).unwrap();
+        let (second, _) = listener.accept().unwrap();
+
+        let mut spectators = Spectators::<QGame<StandardQuoridor>> {
+            follower: Follower::default(),
+            spectators: vec![],
+        };
+        spectators.join(first, Protocol::Text).unwrap();
+        spectators.join(second, Protocol::Binary).unwrap();
+        let game = StandardQuoridor::initial_server();
+        spectators.observe(&GameEvent::GameStart(game.clone(), 0));
+
+        // The game never waits on the stalled spectator, whose queue grows until it is dropped
+        reading.set_nonblocking(true).unwrap();
+        let mut received = vec![];
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while spectators.spectators.len() == 2 {
+            assert!(Instant::now() < deadline);
+            spectators.broadcast(&GameEvent::GameStart(game.clone(), SPECTATOR));
+            let mut chunk = [0u8; 4096];
+            while let Ok(n) = reading.read(&mut chunk) {
+                received.extend_from_slice(&chunk[..n]);
+            }
+        }
+        assert_eq!(spectators.spectators[0].protocol, Protocol::Text);
+        assert!(received.starts_with(b"start spectator e1,e9 - 10,10 0\n"));
+    }
+}
@@ -0,0 +1,327 @@
+use crate::*;
+
+// Cells are written as a column letter and a 1-based row, so player 0 starts on "e1".
+// A wall is named after the cell just below and left of its midpoint, plus 'h' or 'v';
+// walls whose midpoint lies on the bottom or left edge have no such cell and no name.
+
+pub fn cell_to_string(pos: Position) -> String {
+    format!("{}{}", (b'a' + pos.x) as char, pos.y as u32 + 1)
+}
+
+fn split_cell(s: &str) -> Result<(Position, &str), ()> {
+    let column = *s.as_bytes().first().ok_or(())?;
+    if !column.is_ascii_lowercase() {
+        return Err(());
+    }
+    let digits = s[1..].bytes().take_while(u8::is_ascii_digit).count();
+    let row = s[1..1 + digits].parse::<u8>().map_err(|_| ())?;
+    if row == 0 {
+        return Err(());
+    }
+    Ok((Position::from((column - b'a', row - 1)), &s[1 + digits..]))
+}
+
+pub fn parse_cell(s: &str) -> Result<Position, ()> {
+    match split_cell(s)? {
+        (pos, "") => Ok(pos),
+        _ => Err(()),
+    }
+}
+
+pub fn wall_to_string<Rb: Rulebook>(wall: Wall) -> Result<String, ()> {
+    let x = if Rb::TOROIDAL {
+        (wall.position.x % Rb::BOARD_SIZE + Rb::BOARD_SIZE - 1) % Rb::BOARD_SIZE
+    } else {
+        wall.position.x.checked_sub(1).ok_or(())?
+    };
+    let y = wall.position.y.checked_sub(1).ok_or(())?;
+    if x >= 26 {
+        return Err(());
+    }
+    let orientation = match wall.orientation {
+        Orientation::Horizontal => 'h',
+        Orientation::Vertical => 'v',
+    };
+    Ok(format!("{}{}", cell_to_string(Position::from((x, y))), orientation))
+}
+
+pub fn parse_wall<Rb: Rulebook>(s: &str) -> Result<Wall, ()> {
+    let (cell, rest) = split_cell(s)?;
+    let x = if Rb::TOROIDAL {
+        (cell.x + 1) % Rb::BOARD_SIZE
+    } else {
+        cell.x + 1
+    };
+    let position = Position::from((x, cell.y + 1));
+    match rest {
+        "h" => Ok(Wall::horizontal(position)),
+        "v" => Ok(Wall::vertical(position)),
+        _ => Err(()),
+    }
+}
+
+// Pawn moves only name the target when each player has a single pawn
+pub fn move_to_string<Rb: Rulebook>(qmove: Move) -> Result<String, ()> {
+    Ok(match qmove {
+        Move::MovePawn(start, target) => {
+            if Rb::PAWN_COUNT == Rb::PLAYER_COUNT {
+                cell_to_string(target)
+            } else {
+                cell_to_string(start) + &cell_to_string(target)
+            }
+        }
+        Move::PlaceWall(wall) => wall_to_string::<Rb>(wall)?,
+        Move::RemoveWall(wall) => format!("-{}", wall_to_string::<Rb>(wall)?),
+        Move::MoveWall(from, to) => {
+            format!("{}>{}", wall_to_string::<Rb>(from)?, wall_to_string::<Rb>(to)?)
+        }
+    })
+}
+
+pub fn parse_move<Rb: Rulebook>(game: &QGame<Rb>, s: &str) -> Result<Move, ()> {
+    if let Some(wall) = s.strip_prefix('-') {
+        return Ok(Move::RemoveWall(parse_wall::<Rb>(wall)?));
+    }
+    if let Some(split) = s.find('>') {
+        return Ok(Move::MoveWall(
+            parse_wall::<Rb>(&s[..split])?,
+            parse_wall::<Rb>(&s[split + 1..])?,
+        ));
+    }
+    if s.ends_with('h') || s.ends_with('v') {
+        return Ok(Move::PlaceWall(parse_wall::<Rb>(s)?));
+    }
+
+    let (first, rest) = split_cell(s)?;
+    if !rest.is_empty() {
+        return Ok(Move::MovePawn(first, parse_cell(rest)?));
+    }
+    let pawns_per_player = Rb::PAWN_COUNT / Rb::PLAYER_COUNT;
+    let mut own = game
+        .pawn_positions
+        .iter()
+        .filter(|(&id, _)| id / pawns_per_player == game.turn_of);
+    match (own.next(), own.next()) {
+        (Some((_, &start)), None) => Ok(Move::MovePawn(start, first)),
+        _ => Err(()),
+    }
+}
+
+// Pawns by id, walls (with their owner after a colon), wall counts and the side to move:
+// "e1,e9 e3h:0,f5v:1 9,9 0"
+pub fn position_to_string<Rb: Rulebook>(game: &QGame<Rb>) -> Result<String, ()> {
+    let pawns = (0..Rb::PAWN_COUNT)
+        .filter_map(|id| game.pawn_positions.get_by_left(&id))
+        .map(|&pos| cell_to_string(pos))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let mut walls = game
+        .walls
+        .iter()
+        .map(|&wall| {
+            let name = wall_to_string::<Rb>(wall)?;
+            Ok(match game.wall_owners.get(&wall) {
+                Some(owner) => format!("{}:{}", name, owner),
+                None => name,
+            })
+        })
+        .collect::<Result<Vec<_>, ()>>()?;
+    walls.sort();
+    let walls = if walls.is_empty() {
+        String::from("-")
+    } else {
+        walls.join(",")
+    };
+
+    let counts = game
+        .wall_counts
+        .iter()
+        .map(|count| count.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    Ok(format!("{} {} {} {}", pawns, walls, counts, game.turn_of))
+}
+
+pub fn parse_position<Rb: Rulebook>(s: &str) -> Result<QGame<Rb>, ()> {
+    let fields = s.split_whitespace().collect::<Vec<_>>();
+    if fields.len() != 4 {
+        return Err(());
+    }
+
+    let mut game = Rb::initial_server();
+    game.pawn_positions.clear();
+    for (id, cell) in fields[0].split(',').enumerate() {
+        game.pawn_positions.insert(id as PawnID, parse_cell(cell)?);
+    }
+    if game.pawn_positions.len() != Rb::PAWN_COUNT as usize {
+        return Err(());
+    }
+
+    game.walls.clear();
+    game.wall_owners.clear();
+    if fields[1] != "-" {
+        for token in fields[1].split(',') {
+            let mut parts = token.splitn(2, ':');
+            let wall = parse_wall::<Rb>(parts.next().ok_or(())?)?;
+            if let Some(owner) = parts.next() {
+                game.wall_owners
+                    .insert(wall, owner.parse().map_err(|_| ())?);
+            }
+            game.walls.insert(wall);
+        }
+    }
+
+    game.wall_counts = fields[2]
+        .split(',')
+        .map(|count| count.parse().map_err(|_| ()))
+        .collect::<Result<_, _>>()?;
+    if game.wall_counts.len() != Rb::PLAYER_COUNT as usize {
+        return Err(());
+    }
+
+    game.turn_of = fields[3].parse().map_err(|_| ())?;
+    if game.turn_of >= Rb::PLAYER_COUNT {
+        return Err(());
+    }
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::legal_moves;
+    use crate::rulebooks::{FreeQuoridor, MovingWallsQuoridor, StandardQuoridor, ToroidalQuoridor};
+
+    #[test]
+    fn cells_round_trip() {
+        assert_eq!(cell_to_string((4, 0).into()), "e1");
+        assert_eq!(cell_to_string((4, 8).into()), "e9");
+        for x in 0..9 {
+            for y in 0..9 {
+                let pos = Position::from((x, y));
+                assert_eq!(parse_cell(&cell_to_string(pos)), Ok(pos));
+            }
+        }
+        for bad in ["", "e", "e0", "E1", "1e", "e1h", "e-1"].iter() {
+            assert_eq!(parse_cell(bad), Err(()), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn walls_round_trip() {
+        assert_eq!(
+            wall_to_string::<StandardQuoridor>(Wall::horizontal((1, 1).into())),
+            Ok("a1h".into())
+        );
+        assert_eq!(
+            wall_to_string::<StandardQuoridor>(Wall::vertical((8, 8).into())),
+            Ok("h8v".into())
+        );
+        for x in 1..9 {
+            for y in 1..9 {
+                for &wall in [
+                    Wall::horizontal((x, y).into()),
+                    Wall::vertical((x, y).into()),
+                ]
+                .iter()
+                {
+                    let text = wall_to_string::<StandardQuoridor>(wall).unwrap();
+                    assert_eq!(parse_wall::<StandardQuoridor>(&text), Ok(wall));
+                }
+            }
+        }
+        assert_eq!(parse_wall::<StandardQuoridor>("e3"), Err(()));
+        assert_eq!(parse_wall::<StandardQuoridor>("e3x"), Err(()));
+    }
+
+    #[test]
+    fn edge_walls_have_no_name() {
+        // FreeQuoridor takes any wall, including ones whose midpoint is on the board edge
+        for &wall in [
+            Wall::horizontal((0, 3).into()),
+            Wall::vertical((3, 0).into()),
+            Wall::vertical((255, 3).into()),
+        ]
+        .iter()
+        {
+            assert_eq!(wall_to_string::<FreeQuoridor>(wall), Err(()));
+        }
+
+        let mut game = FreeQuoridor::initial_server();
+        FreeQuoridor::apply_move(&mut game, Move::PlaceWall(Wall::horizontal((0, 3).into())));
+        assert_eq!(position_to_string(&game), Err(()));
+    }
+
+    #[test]
+    fn toroidal_walls_name_the_seam() {
+        // The seam is the boundary left of column 0, so its walls are written from the last column
+        let seam = Wall::vertical((0, 4).into());
+        assert_eq!(wall_to_string::<ToroidalQuoridor>(seam), Ok("i4v".into()));
+        for x in 0..9 {
+            let wall = Wall::horizontal((x, 3).into());
+            let text = wall_to_string::<ToroidalQuoridor>(wall).unwrap();
+            assert_eq!(parse_wall::<ToroidalQuoridor>(&text), Ok(wall));
+        }
+    }
+
+    #[test]
+    fn moves_round_trip() {
+        let mut game = MovingWallsQuoridor::initial_server();
+        let wall = Wall::horizontal((3, 4).into());
+        MovingWallsQuoridor::apply_move(&mut game, Move::PlaceWall(wall));
+        MovingWallsQuoridor::apply_move(&mut game, Move::MovePawn((4, 8).into(), (4, 7).into()));
+
+        let mut moves = legal_moves(&game);
+        moves.push(Move::RemoveWall(wall));
+        moves.push(Move::MoveWall(wall, Wall::vertical((6, 6).into())));
+        for qmove in moves {
+            let text = move_to_string::<MovingWallsQuoridor>(qmove).unwrap();
+            assert_eq!(parse_move(&game, &text), Ok(qmove), "{}", text);
+        }
+        assert_eq!(
+            move_to_string::<MovingWallsQuoridor>(Move::RemoveWall(wall)),
+            Ok("-c4h".into())
+        );
+        assert_eq!(
+            parse_move(&game, "e2"),
+            Ok(Move::MovePawn((4, 0).into(), (4, 1).into()))
+        );
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        let mut game = StandardQuoridor::initial_server();
+        assert_eq!(position_to_string(&game), Ok("e1,e9 - 10,10 0".into()));
+
+        StandardQuoridor::apply_move(&mut game, Move::PlaceWall(Wall::horizontal((5, 3).into())));
+        StandardQuoridor::apply_move(&mut game, Move::PlaceWall(Wall::vertical((6, 5).into())));
+        StandardQuoridor::apply_move(&mut game, Move::MovePawn((4, 0).into(), (4, 1).into()));
+        let text = position_to_string(&game).unwrap();
+        assert_eq!(text, "e2,e9 e3h:0,f5v:1 9,9 1");
+
+        let parsed = parse_position::<StandardQuoridor>(&text).unwrap();
+        assert_eq!(parsed.pawn_positions, game.pawn_positions);
+        assert_eq!(parsed.walls, game.walls);
+        assert_eq!(parsed.wall_owners, game.wall_owners);
+        assert_eq!(parsed.wall_counts, game.wall_counts);
+        assert_eq!(parsed.turn_of, game.turn_of);
+    }
+
+    #[test]
+    fn malformed_positions_are_refused() {
+        for bad in [
+            "e1,e9 - 10,10",
+            "e1 - 10,10 0",
+            "e1,e9 - 10 0",
+            "e1,e9 - 10,10 2",
+            "e1,e9 e3x 10,10 0",
+            "e1,e9 e3h:x 10,10 0",
+        ]
+        .iter()
+        {
+            assert!(parse_position::<StandardQuoridor>(bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::*;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub rulebook: String,
+    pub players: Vec<String>,
+    // The starting position in notation; records without one start from the rulebook's usual setup
+    #[serde(default)]
+    pub start: Option<String>,
+    // The rulebook's metadata at the start, like a random setup's seed or the fog's sight radius,
+    // which the notation leaves out
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+    pub moves: Vec<Move>,
+    pub winner: Option<PlayerID>,
+}
+
+impl GameRecord {
+    pub fn new(rulebook: &str, players: Vec<String>) -> Self {
+        GameRecord {
+            rulebook: rulebook.into(),
+            players,
+            start: None,
+            metadata: None,
+            moves: vec![],
+            winner: None,
+        }
+    }
+
+    // Every position of the game, starting with the initial one; fails with the index of the first illegal move,
+    // or 0 if the starting position can't be read
+    pub fn positions<Rb: Rulebook<Move = Move>>(&self) -> Result<Vec<QGame<Rb>>, usize> {
+        self.positions_from(self.initial()?)
+    }
+
+    pub fn initial<Rb: Rulebook>(&self) -> Result<QGame<Rb>, usize> {
+        let mut game = match &self.start {
+            Some(start) => notation::parse_position(start).map_err(|_| 0usize)?,
+            None => Rb::initial_server(),
+        };
+        if let Some(metadata) = &self.metadata {
+            game.metadata = serde_json::from_value(metadata.clone()).map_err(|_| 0usize)?;
+        }
+        Ok(game)
+    }
+
+    pub fn positions_from<Rb: Rulebook<Move = Move>>(
+        &self,
+        initial: QGame<Rb>,
+    ) -> Result<Vec<QGame<Rb>>, usize> {
+        let mut positions = vec![initial];
+        for (i, &qmove) in self.moves.iter().enumerate() {
+            let mut game = Clone::clone(positions.last().unwrap());
+            if Rb::validate_move(&game, qmove).is_err() {
+                return Err(i);
+            }
+            Rb::apply_move(&mut game, qmove);
+            positions.push(game);
+        }
+        Ok(positions)
+    }
+}
+
+// Passes a seat's events through unchanged, noting the start and every move in `record` on the way.
+// The seat should see the whole board, or hidden moves go unrecorded.
+pub fn recorder<Rb: Rulebook<Move = Move>>(
+    core: AgentCore<QGame<Rb>>,
+    record: Arc<Mutex<GameRecord>>,
+) -> (
+    AgentCore<QGame<Rb>>,
+    impl Send + Sync + FnMut() -> Result<(), Box<dyn Error>>,
+) {
+    let AgentCore {
+        event_channel: upstream,
+        move_channel,
+    } = core;
+    let (events, event_channel) = crossbeam_channel::unbounded();
+    let relay = move || {
+        while let Ok(event) = upstream.try_recv() {
+            if let Ok(mut record) = record.lock() {
+                match &event {
+                    GameEvent::GameStart(game, _) if record.moves.is_empty() => {
+                        record.start = Some(
+                            notation::position_to_string(game)
+                                .map_err(|_| "start position has no notation")?,
+                        );
+                        record.metadata = Some(serde_json::to_value(&game.metadata)?);
+                    }
+                    GameEvent::MoveHappened(qmove) => record.moves.push(*qmove),
+                    _ => {}
+                }
+            }
+            events.send(event).map_err(|_| "agent closed")?;
+        }
+        Ok(())
+    };
+    (
+        AgentCore {
+            event_channel,
+            move_channel,
+        },
+        relay,
+    )
+}
@@ -0,0 +1,275 @@
+use crate::record::{recorder, GameRecord};
+use crate::*;
+use crossbeam_channel::{Receiver, Sender};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+pub type GameFn = Box<dyn Send + Sync + FnMut() -> Result<MoveResult, Box<dyn Error>>>;
+
+struct Seat<Rb: Rulebook> {
+    inner: AgentCore<QGame<Rb>>,
+    events: Sender<GameEvent<QGame<Rb>>>,
+    moves: Receiver<Rb::Move>,
+    game: Option<QGame<Rb>>,
+    side: PlayerID,
+}
+
+impl<Rb: Rulebook> Seat<Rb> {
+    fn forward_moves(&self) -> Result<(), Box<dyn Error>> {
+        while let Ok(qmove) = self.moves.try_recv() {
+            self.inner
+                .move_channel
+                .send(qmove)
+                .map_err(|_| "game closed")?;
+        }
+        Ok(())
+    }
+
+    fn forward_events(&mut self) {
+        while let Ok(event) = self.inner.event_channel.try_recv() {
+            let event = match event {
+                GameEvent::GameStart(game, id) => {
+                    let view = Rb::redact(&game, id);
+                    self.game = Some(game);
+                    self.side = id;
+                    GameEvent::GameStart(view, id)
+                }
+                // Moves may reveal or hide parts of the board, so the seat gets a fresh view instead
+                GameEvent::MoveHappened(qmove) => match &mut self.game {
+                    Some(game) => {
+                        Rb::apply_move(game, qmove);
+                        GameEvent::GameStart(Rb::redact(game, self.side), self.side)
+                    }
+                    None => GameEvent::MoveHappened(qmove),
+                },
+                event => event,
+            };
+            self.events.send(event).ok();
+        }
+    }
+}
+
+pub fn new_game<Rb: Rulebook>() -> (Vec<AgentCore<QGame<Rb>>>, GameFn) {
+    let (cores, game_fn) = tbmp_core::new_game::<QGame<Rb>>();
+    redact(cores, Box::new(game_fn))
+}
+
+// Like new_game, but the start and every move are noted in `record` before any seat's view is redacted
+pub fn new_recorded_game<Rb: Rulebook<Move = Move>>(
+    record: Arc<Mutex<GameRecord>>,
+) -> (Vec<AgentCore<QGame<Rb>>>, GameFn) {
+    new_recorded_game_from(record, Rb::initial_server())
+}
+
+pub fn new_recorded_game_from<Rb: Rulebook<Move = Move>>(
+    record: Arc<Mutex<GameRecord>>,
+    start: QGame<Rb>,
+) -> (Vec<AgentCore<QGame<Rb>>>, GameFn) {
+    start_next_game_from(Some(start));
+    let (mut cores, mut game_fn) = tbmp_core::new_game::<QGame<Rb>>();
+    start_next_game_from::<Rb>(None);
+    let (first, mut recording) = recorder(cores.remove(0), record);
+    cores.insert(0, first);
+
+    recording().ok();
+    let t = move || {
+        let result = game_fn()?;
+        recording()?;
+        Ok(result)
+    };
+    redact(cores, Box::new(t))
+}
+
+fn redact<Rb: Rulebook>(
+    cores: Vec<AgentCore<QGame<Rb>>>,
+    mut game_fn: GameFn,
+) -> (Vec<AgentCore<QGame<Rb>>>, GameFn) {
+    if !Rb::HIDDEN_INFORMATION {
+        return (cores, game_fn);
+    }
+
+    let mut seats = vec![];
+    let mut outer = vec![];
+    for inner in cores {
+        let (etx, erx) = crossbeam_channel::unbounded();
+        let (mtx, mrx) = crossbeam_channel::unbounded();
+        seats.push(Seat {
+            inner,
+            events: etx,
+            moves: mrx,
+            game: None,
+            side: 0,
+        });
+        outer.push(AgentCore {
+            event_channel: erx,
+            move_channel: mtx,
+        });
+    }
+
+    let mut pump = move || -> Result<(), Box<dyn Error>> {
+        for seat in seats.iter_mut() {
+            seat.forward_moves()?;
+            seat.forward_events();
+        }
+        Ok(())
+    };
+
+    pump().ok();
+    let t = move || {
+        pump()?;
+        let result = game_fn();
+        pump()?;
+        result
+    };
+    (outer, Box::new(t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rulebooks::{FogQuoridor, RandomSetupQuoridor};
+
+    #[test]
+    fn seat_only_sees_its_view() {
+        let (server_events, inner_events) = crossbeam_channel::unbounded();
+        let (inner_moves, _server_moves) = crossbeam_channel::unbounded();
+        let (events, outer_events) = crossbeam_channel::unbounded();
+        let (_outer_moves, moves) = crossbeam_channel::unbounded();
+        let mut seat = Seat::<FogQuoridor> {
+            inner: AgentCore {
+                event_channel: inner_events,
+                move_channel: inner_moves,
+            },
+            events,
+            moves,
+            game: None,
+            side: 0,
+        };
+
+        let mut game = FogQuoridor::initial_server();
+        let hidden = Wall::horizontal((4, 7).into());
+        game.walls.insert(hidden);
+        game.wall_owners.insert(hidden, 1);
+        server_events.send(GameEvent::GameStart(game, 0)).unwrap();
+        let far = Wall::horizontal((1, 6).into());
+        server_events
+            .send(GameEvent::MoveHappened(Move::MovePawn(
+                (4, 0).into(),
+                (4, 1).into(),
+            )))
+            .unwrap();
+        server_events
+            .send(GameEvent::MoveHappened(Move::PlaceWall(far)))
+            .unwrap();
+        seat.forward_events();
+
+        let views = outer_events
+            .try_iter()
+            .map(|event| match event {
+                GameEvent::GameStart(view, 0) => view,
+                _ => panic!("expected a fresh view"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(views.len(), 3);
+        for view in views.iter() {
+            assert!(!view.walls.contains(&hidden));
+            assert!(!view.walls.contains(&far));
+        }
+        // The seat still tracks the whole game behind the views
+        let game = seat.game.as_ref().unwrap();
+        assert!(game.walls.contains(&hidden) && game.walls.contains(&far));
+        assert_eq!(views[2].wall_counts, game.wall_counts);
+    }
+
+    #[test]
+    fn records_see_the_whole_board() {
+        let record = Arc::new(Mutex::new(GameRecord::new("fog", vec![])));
+        let (cores, mut game_fn) = new_recorded_game::<FogQuoridor>(record.clone());
+        let moves = [
+            Move::MovePawn((4, 0).into(), (4, 1).into()),
+            Move::PlaceWall(Wall::horizontal((4, 7).into())),
+        ];
+        for (core, &qmove) in cores.iter().zip(moves.iter()) {
+            core.move_channel.send(qmove).unwrap();
+            assert!(matches!(game_fn().unwrap(), MoveResult::Continue));
+        }
+
+        let record = record.lock().unwrap();
+        assert_eq!(record.moves, moves.to_vec());
+        let positions = record.positions::<FogQuoridor>().unwrap();
+        assert!(positions[2]
+            .walls
+            .contains(&Wall::horizontal((4, 7).into())));
+    }
+
+    #[test]
+    fn records_keep_random_setups() {
+        let record = Arc::new(Mutex::new(GameRecord::new("random", vec![])));
+        let (cores, _) = new_recorded_game::<RandomSetupQuoridor>(record.clone());
+        let start = match cores[0].event_channel.try_recv() {
+            Ok(GameEvent::GameStart(game, _)) => game,
+            _ => panic!("expected the start"),
+        };
+        let initial = record
+            .lock()
+            .unwrap()
+            .initial::<RandomSetupQuoridor>()
+            .unwrap();
+        assert_eq!(
+            notation::position_to_string(&initial).unwrap(),
+            notation::position_to_string(&start).unwrap()
+        );
+    }
+
+    #[test]
+    fn records_keep_rulebook_metadata() {
+        let options = [("sight".to_string(), "4".to_string())];
+        let start = crate::rulebooks::start_with_options::<FogQuoridor>(&options).unwrap();
+        let record = Arc::new(Mutex::new(GameRecord::new("fog", vec![])));
+        new_recorded_game_from(record.clone(), start);
+
+        let text = serde_json::to_string(&*record.lock().unwrap()).unwrap();
+        let record: GameRecord = serde_json::from_str(&text).unwrap();
+        assert_eq!(
+            record
+                .initial::<FogQuoridor>()
+                .unwrap()
+                .metadata
+                .sight_radius,
+            4
+        );
+
+        let record = Arc::new(Mutex::new(GameRecord::new("random", vec![])));
+        new_recorded_game_from(record.clone(), RandomSetupQuoridor::from_seed(11));
+        let initial = record
+            .lock()
+            .unwrap()
+            .initial::<RandomSetupQuoridor>()
+            .unwrap();
+        assert_eq!(initial.metadata.seed, 11);
+    }
+
+    #[test]
+    fn games_start_from_their_rule_options() {
+        let options = [("seed".to_string(), "7".to_string())];
+        let start = crate::rulebooks::start_with_options::<RandomSetupQuoridor>(&options).unwrap();
+        assert_eq!(start.metadata.seed, 7);
+
+        let record = Arc::new(Mutex::new(GameRecord::new("random", vec![])));
+        let (cores, _) = new_recorded_game_from(record, start);
+        for core in cores.iter() {
+            match core.event_channel.try_recv() {
+                Ok(GameEvent::GameStart(game, _)) => assert_eq!(game.metadata.seed, 7),
+                _ => panic!("expected the start"),
+            }
+        }
+
+        let options = [("sight".to_string(), "4".to_string())];
+        let fog = crate::rulebooks::start_with_options::<FogQuoridor>(&options).unwrap();
+        assert_eq!(fog.metadata.sight_radius, 4);
+        for bad in [("sight", "far"), ("seed", "7")].iter() {
+            let options = [(bad.0.to_string(), bad.1.to_string())];
+            assert!(crate::rulebooks::start_with_options::<FogQuoridor>(&options).is_err());
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::rulebooks::*;
+use crate::*;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FogQuoridor;
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct FogMetadata {
+    pub sight_radius: u8,
+}
+
+impl FogQuoridor {
+    pub fn cell_visible(game: &QGame<Self>, player: PlayerID, pos: Position) -> bool {
+        let sight = game.metadata.sight_radius as i8;
+        game.pawn_positions
+            .iter()
+            .filter(|(&id, _)| id == player)
+            .any(|(_, pawn)| {
+                (pawn.x as i8 - pos.x as i8).abs() <= sight
+                    && (pawn.y as i8 - pos.y as i8).abs() <= sight
+            })
+    }
+
+    pub fn wall_visible(game: &QGame<Self>, player: PlayerID, wall: &Wall) -> bool {
+        if game.wall_owners.get(wall) == Some(&player) {
+            return true;
+        }
+        // A wall touches the four cells around its centre point
+        let (x, y) = (wall.position.x, wall.position.y);
+        let cells = [
+            (x, y),
+            (x.wrapping_sub(1), y),
+            (x, y.wrapping_sub(1)),
+            (x.wrapping_sub(1), y.wrapping_sub(1)),
+        ];
+        cells
+            .iter()
+            .any(|&cell| Self::cell_visible(game, player, cell.into()))
+    }
+}
+
+impl Rulebook for FogQuoridor {
+    const PLAYER_COUNT: u8 = 2;
+    const PAWN_COUNT: u8 = 2;
+    const HIDDEN_INFORMATION: bool = true;
+    type Move = Move;
+    type Metadata = FogMetadata;
+
+    fn validate_move(game: &QGame<Self>, qmove: Move) -> Result<(), ()> {
+        match qmove {
+            Move::PlaceWall(wall) => {
+                if game.wall_counts[game.turn_of as usize] == 0 {
+                    Err(())
+                } else {
+                    check_wall_placement(game, wall)
+                }
+            }
+            Move::MovePawn(start_pos, target_pos) => check_pawn_move(game, start_pos, target_pos),
+            Move::RemoveWall(_) => Err(()),
+            Move::MoveWall(_, _) => Err(()),
+        }
+    }
+
+    fn apply_move(game: &mut QGame<Self>, qmove: Move) -> MoveResult {
+        apply_standard_move(game, qmove);
+        finish_standard_turn(game)
+    }
+
+    fn initial_server() -> QGame<Self> {
+        let mut pawns = BiMap::with_capacity(2);
+        pawns.insert(0, Position::from((4, 0)));
+        pawns.insert(1, Position::from((4, 8)));
+        QGame::<Self> {
+            wall_counts: vec![10, 10],
+            pawn_positions: pawns,
+            walls: HashSet::new(),
+            wall_owners: HashMap::new(),
+            turn_of: 0,
+            metadata: FogMetadata { sight_radius: 2 },
+        }
+    }
+
+    fn set_option(game: &mut QGame<Self>, key: &str, value: &str) -> Result<(), ()> {
+        match key {
+            "sight" => game.metadata.sight_radius = value.parse().map_err(|_| ())?,
+            _ => return Err(()),
+        }
+        Ok(())
+    }
+
+    fn redact(game: &QGame<Self>, player: PlayerID) -> QGame<Self> {
+        let mut view = Clone::clone(game);
+        view.walls.retain(|wall| Self::wall_visible(game, player, wall));
+        view.wall_owners
+            .retain(|wall, _| Self::wall_visible(game, player, wall));
+        view
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_hides_far_opponent_walls() {
+        let mut game = FogQuoridor::initial_server();
+        let own = Wall::horizontal((1, 5).into());
+        let theirs = Wall::horizontal((4, 7).into());
+        FogQuoridor::apply_move(&mut game, Move::PlaceWall(own));
+        FogQuoridor::apply_move(&mut game, Move::PlaceWall(theirs));
+
+        let view = FogQuoridor::redact(&game, 0);
+        assert!(view.walls.contains(&own));
+        assert!(!view.walls.contains(&theirs));
+        assert!(!view.wall_owners.contains_key(&theirs));
+
+        let view = FogQuoridor::redact(&game, 1);
+        assert!(view.walls.contains(&theirs));
+        assert!(!view.walls.contains(&own));
+    }
+
+    #[test]
+    fn redact_shows_opponent_walls_in_sight() {
+        let mut game = FogQuoridor::initial_server();
+        FogQuoridor::apply_move(&mut game, Move::MovePawn((4, 0).into(), (4, 1).into()));
+        let near = Wall::horizontal((5, 2).into());
+        FogQuoridor::apply_move(&mut game, Move::PlaceWall(near));
+
+        let view = FogQuoridor::redact(&game, 0);
+        assert!(view.walls.contains(&near));
+        assert_eq!(view.wall_owners.get(&near), Some(&1));
+        assert_eq!(view.pawn_positions, game.pawn_positions);
+    }
+}
@@ -21,6 +21,7 @@ impl Rulebook for FreeQuoridor {
         match qmove {
             Move::PlaceWall(wall) => {
                 game.walls.insert(wall);
+                game.wall_owners.insert(wall, game.turn_of);
             }
             Move::MovePawn(start_pos, end_pos) => {
                 let id = game.pawn_positions.remove_by_right(&start_pos).unwrap().0;
@@ -28,10 +29,14 @@ impl Rulebook for FreeQuoridor {
             }
             Move::RemoveWall(wall) => {
                 game.walls.remove(&wall);
+                game.wall_owners.remove(&wall);
             }
             Move::MoveWall(start, end) => {
                 game.walls.remove(&start);
                 game.walls.insert(end);
+                if let Some(owner) = game.wall_owners.remove(&start) {
+                    game.wall_owners.insert(end, owner);
+                }
             }
         }
         match game.metadata.turns_left.checked_sub(1) {
@@ -58,6 +63,7 @@ impl Rulebook for FreeQuoridor {
             wall_counts: vec![10, 10],
             pawn_positions: pawns,
             walls: HashSet::new(),
+            wall_owners: HashMap::new(),
             turn_of: 0,
             metadata: FreeQuoridorMetadata { turns_left: 1 },
         }
@@ -1,7 +1,26 @@
+pub mod fog_rulebook;
 pub mod free_rulebook;
+pub mod moving_walls_rulebook;
+pub mod random_setup_rulebook;
+pub mod sized_rulebook;
 pub mod standard_rulebook;
+pub mod toroidal_rulebook;
+pub use fog_rulebook::*;
 pub use free_rulebook::*;
+pub use moving_walls_rulebook::*;
+pub use random_setup_rulebook::*;
+pub use sized_rulebook::*;
 pub use standard_rulebook::*;
+pub use toroidal_rulebook::*;
+
+// A fresh start with rule options applied, e.g. "seed=7" for a random setup
+pub fn start_with_options<Rb: crate::Rulebook>(options: &[(String, String)]) -> Result<crate::QGame<Rb>, String> {
+    let mut game = Rb::initial_server();
+    for (key, value) in options {
+        Rb::set_option(&mut game, key, value).map_err(|_| format!("Unknown rule option {}={}", key, value))?;
+    }
+    Ok(game)
+}
 
 #[macro_export]
 macro_rules! generate_rulebook {
@@ -58,6 +77,30 @@ macro_rules! generate_rulebook {
                 }
             }
 
+            pub(crate) fn is_toroidal(&self) -> bool {
+                match self {
+                    $(
+                        Self::$rulebook_ident(_) => $rulebook_ident::TOROIDAL,
+                    )*
+                }
+            }
+
+            pub(crate) fn moves_walls(&self) -> bool {
+                match self {
+                    $(
+                        Self::$rulebook_ident(_) => $rulebook_ident::MOVABLE_WALLS,
+                    )*
+                }
+            }
+
+            pub(crate) fn analyse(&self, top_n: usize, limits: $crate::ai::SearchLimits) -> Vec<$crate::ai::MoveAnalysis> {
+                match self {
+                    $(
+                        Self::$rulebook_ident(g) => $crate::ai::analyse(g, top_n, limits),
+                    )*
+                }
+            }
+
             pub(crate) fn apply_move(&mut self, qmove: &RulebookMove) {
                 match self {
                     $(
@@ -151,12 +194,85 @@ macro_rules! generate_rulebook {
                 }
             }
 
-            pub(crate) fn connect(addr: std::net::SocketAddr, game_type: QGameType) -> (QAgent, Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>) {
+            pub(crate) fn bot(self, config: $crate::ai::BotConfig) -> Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>> {
+                match self {
+                    $(
+                        Self::$rulebook_ident(c) => Box::new($crate::ai::bot_agent(c, config.build::<$rulebook_ident>())),
+                    )*
+                }
+            }
+
+            pub(crate) fn engine(self, command: &str, limits: $crate::engine::GoLimits) -> Result<Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>, Box<dyn Error>> {
+                match self {
+                    $(
+                        Self::$rulebook_ident(c) => Ok(Box::new($crate::engine::engine_agent::<$rulebook_ident>(c, command, stringify!($rulebook_ident), limits)?)),
+                    )*
+                }
+            }
+
+            pub(crate) fn serve(self, stream: std::net::TcpStream, protocol: $crate::net::Protocol) -> Result<Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>, Box<dyn Error>> {
+                match self {
+                    $(
+                        Self::$rulebook_ident(c) => Ok(Box::new($crate::net::serve_agent(stream, c, protocol)?)),
+                    )*
+                }
+            }
+
+            // Joins a game on a server or host, privately with whoever gives the same code if there is one
+            pub(crate) fn connect(addr: std::net::SocketAddr, game_type: QGameType, code: Option<String>) -> Result<(QAgent, Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>), Box<dyn Error>> {
+                let request = $crate::net::MatchRequest {
+                    rulebook: game_type.name().into(),
+                    options: vec![],
+                    code,
+                    watch: None,
+                    resume: None,
+                };
                 match game_type {
                     $(
                         QGameType::$rulebook_ident => {
-                            let (c, t) = tbmp::remote_agent::connect(addr);
-                            (QAgent::$rulebook_ident(c), Box::new(t) as Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>)
+                            let (c, t) = $crate::net::connect(addr, &request)?;
+                            Ok((QAgent::$rulebook_ident(c), Box::new(t) as Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>))
+                        }
+                    )*
+                }
+            }
+
+            // Follows a running game read-only; the agent is told it sits in the net::SPECTATOR seat
+            pub(crate) fn watch(addr: std::net::SocketAddr, game_type: QGameType, game: String) -> Result<(QAgent, Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>), Box<dyn Error>> {
+                let request = $crate::net::MatchRequest {
+                    rulebook: game_type.name().into(),
+                    options: vec![],
+                    code: None,
+                    watch: Some(game),
+                    resume: None,
+                };
+                match game_type {
+                    $(
+                        QGameType::$rulebook_ident => {
+                            let (c, t) = $crate::net::connect(addr, &request)?;
+                            Ok((QAgent::$rulebook_ident(c), Box::new(t) as Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>))
+                        }
+                    )*
+                }
+            }
+
+            pub(crate) fn seat(self, stream: std::net::TcpStream, protocol: $crate::net::Protocol, timeouts: $crate::net::Timeouts) -> Result<(std::sync::Arc<std::sync::Mutex<dyn $crate::net::Session>>, Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>), Box<dyn Error>> {
+                match self {
+                    $(
+                        Self::$rulebook_ident(c) => {
+                            let (session, t) = $crate::net::seat(stream, protocol, c, timeouts)?;
+                            Ok((session, Box::new(t)))
+                        }
+                    )*
+                }
+            }
+
+            pub(crate) fn tap(self) -> (QAgent, std::sync::Arc<std::sync::Mutex<dyn $crate::net::Audience>>, Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>) {
+                match self {
+                    $(
+                        Self::$rulebook_ident(c) => {
+                            let (c, audience, t) = $crate::net::tap(c);
+                            (Self::$rulebook_ident(c), audience, Box::new(t))
                         }
                     )*
                 }
@@ -164,29 +280,54 @@ macro_rules! generate_rulebook {
         }
 
         pub(crate) trait AgentList {
-            fn host(self, socket: u16, game_type: QGameType) -> Vec<Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>>;
+            fn host(self, listener: &std::net::TcpListener) -> Result<Vec<Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>>, Box<dyn Error>>;
         }
 
         impl AgentList for Vec<QAgent> {
-            fn host(self, socket: u16, game_type: QGameType) ->  Vec<Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>> {
-                match game_type {
-                    $(
-                        QGameType::$rulebook_ident => {
-                            self
-                                .into_iter()
-                                .map(|core| {
-                                    match core {
-                                        QAgent::$rulebook_ident(c) => {
-                                            Box::new(tbmp::remote_agent::host(vec![c], socket).remove(0)) as
-                                                Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>
-                                        },
-                                        _ => unreachable!()
-                                    }
-                                })
-                                .collect()
-                        },
-                    )*
+            // Waits for one connection per agent, seating them in arrival order;
+            // clients asking for another rulebook are turned away. Once the seats are
+            // taken, spectators can join through the same listener.
+            fn host(self, listener: &std::net::TcpListener) -> Result<Vec<Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>>, Box<dyn Error>> {
+                let mut threads = vec![];
+                let mut audience = None;
+                for (seat, core) in self.into_iter().enumerate() {
+                    let (rulebook, hidden) = match &core {
+                        $(
+                            QAgent::$rulebook_ident(_) => (stringify!($rulebook_ident), $rulebook_ident::HIDDEN_INFORMATION),
+                        )*
+                    };
+                    let core = if seat == 0 && !hidden {
+                        let (core, spectators, t) = core.tap();
+                        threads.push(t);
+                        audience = Some(spectators);
+                        core
+                    } else {
+                        core
+                    };
+                    let (stream, protocol) = loop {
+                        let (mut stream, _) = listener.accept()?;
+                        let (request, protocol) = match $crate::net::read_request(&mut stream) {
+                            Ok(request) => request,
+                            Err(_) => continue,
+                        };
+                        let reply = if request.watch.is_some() {
+                            $crate::net::MatchReply::Rejected("the game has not started yet".into())
+                        } else if request.resume.is_some() {
+                            $crate::net::MatchReply::Rejected("there is no game to resume".into())
+                        } else if request.rulebook.is_empty() || request.rulebook == rulebook {
+                            $crate::net::MatchReply::Queued(None)
+                        } else {
+                            $crate::net::MatchReply::Rejected(format!("this game is {}, not {}", rulebook, request.rulebook))
+                        };
+                        let accepted = matches!(reply, $crate::net::MatchReply::Queued(_));
+                        if $crate::net::write_reply(&mut stream, protocol, &reply).is_ok() && accepted {
+                            break (stream, protocol);
+                        }
+                    };
+                    threads.push(core.serve(stream, protocol)?);
                 }
+                threads.push(Box::new($crate::net::spectator_desk(listener, audience)?));
+                Ok(threads)
             }
         }
 
@@ -209,23 +350,96 @@ macro_rules! generate_rulebook {
                 }
             }
 
+            pub(crate) fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$rulebook_ident => stringify!($rulebook_ident),
+                    )*
+                }
+            }
+
+            pub(crate) fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $(
+                        stringify!($rulebook_ident) => Some(Self::$rulebook_ident),
+                    )*
+                    _ => None,
+                }
+            }
+
+            // Spectators would see what the players can't, so games like these can't be watched
+            pub(crate) fn hides_information(&self) -> bool {
+                match self {
+                    $(
+                        Self::$rulebook_ident => $rulebook_ident::HIDDEN_INFORMATION,
+                    )*
+                }
+            }
+
             pub(crate) fn new_game(&self) -> (Vec<QAgent>, Box<dyn Send + Sync + FnMut() -> Result<MoveResult, Box<dyn Error>>>) {
                 match self {
                     $(
                         Self::$rulebook_ident => {
-                            let (cores, t) = tbmp::new_game::<QGame<$rulebook_ident>>();
+                            let (cores, t) = $crate::redaction::new_game::<$rulebook_ident>();
                             (cores
                                 .into_iter()
                                 .map(|core| {
                                     QAgent::$rulebook_ident(core)
                                 })
                                 .collect(),
-                                Box::new(t) as Box<dyn Send + Sync + FnMut() -> Result<MoveResult, Box<dyn Error>>>
+                                t
                             )
                         },
                     )*
                 }
             }
+
+            // Records from the whole board, so games with hidden information are recorded in full
+            pub(crate) fn new_recorded_game(&self, record: std::sync::Arc<std::sync::Mutex<$crate::record::GameRecord>>) -> (Vec<QAgent>, Box<dyn Send + Sync + FnMut() -> Result<MoveResult, Box<dyn Error>>>) {
+                match self {
+                    $(
+                        Self::$rulebook_ident => {
+                            let (cores, t) = $crate::redaction::new_recorded_game::<$rulebook_ident>(record);
+                            (cores
+                                .into_iter()
+                                .map(|core| {
+                                    QAgent::$rulebook_ident(core)
+                                })
+                                .collect(),
+                                t
+                            )
+                        },
+                    )*
+                }
+            }
+
+            pub(crate) fn check_options(&self, options: &[(String, String)]) -> Result<(), String> {
+                match self {
+                    $(
+                        Self::$rulebook_ident => $crate::rulebooks::start_with_options::<$rulebook_ident>(options).map(|_| ()),
+                    )*
+                }
+            }
+
+            // Like new_recorded_game, but the start takes the given rule options
+            pub(crate) fn new_recorded_game_with(&self, record: std::sync::Arc<std::sync::Mutex<$crate::record::GameRecord>>, options: &[(String, String)]) -> Result<(Vec<QAgent>, Box<dyn Send + Sync + FnMut() -> Result<MoveResult, Box<dyn Error>>>), String> {
+                match self {
+                    $(
+                        Self::$rulebook_ident => {
+                            let start = $crate::rulebooks::start_with_options::<$rulebook_ident>(options)?;
+                            let (cores, t) = $crate::redaction::new_recorded_game_from::<$rulebook_ident>(record, start);
+                            Ok((cores
+                                .into_iter()
+                                .map(|core| {
+                                    QAgent::$rulebook_ident(core)
+                                })
+                                .collect(),
+                                t
+                            ))
+                        },
+                    )*
+                }
+            }
         }
 
         impl QGTrait for Quoridor {
@@ -245,6 +459,14 @@ macro_rules! generate_rulebook {
                 }
             }
 
+            fn wall_owners(&self) -> &std::collections::HashMap<Wall, PlayerID> {
+                match self {
+                    $(
+                        Self::$rulebook_ident(g) => &g.wall_owners,
+                    )*
+                }
+            }
+
             fn turn_of(&self) -> PlayerID {
                 match self {
                     $(
@@ -321,6 +543,30 @@ macro_rules! generate_rulebook {
                 }
             }
 
+            pub(crate) fn is_toroidal(&self) -> bool {
+                match self {
+                    $(
+                        Self::$rulebook_ident(_) => $rulebook_ident::TOROIDAL,
+                    )*
+                }
+            }
+
+            pub(crate) fn moves_walls(&self) -> bool {
+                match self {
+                    $(
+                        Self::$rulebook_ident(_) => $rulebook_ident::MOVABLE_WALLS,
+                    )*
+                }
+            }
+
+            pub(crate) fn analyse(&self, top_n: usize, limits: $crate::ai::SearchLimits) -> Vec<$crate::ai::MoveAnalysis> {
+                match self {
+                    $(
+                        Self::$rulebook_ident(g) => $crate::ai::analyse(g, top_n, limits),
+                    )*
+                }
+            }
+
             pub(crate) fn apply_move(&mut self, qmove: &RulebookMove) {
                 match self {
                     $(
@@ -413,6 +659,14 @@ macro_rules! generate_rulebook {
                     )*
                 }
             }
+
+            pub(crate) fn bot(self, config: $crate::ai::BotConfig) -> Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>> {
+                match self {
+                    $(
+                        Self::$rulebook_ident(c) => Box::new($crate::ai::bot_agent(c, config.build::<$rulebook_ident>())),
+                    )*
+                }
+            }
         }
 
         impl RulebookMove {
@@ -430,14 +684,14 @@ macro_rules! generate_rulebook {
                 match self {
                     $(
                         Self::$rulebook_ident => {
-                            let (cores, t) = tbmp_core::new_game::<QGame<$rulebook_ident>>();
+                            let (cores, t) = $crate::redaction::new_game::<$rulebook_ident>();
                             (cores
                                 .into_iter()
                                 .map(|core| {
                                     QAgent::$rulebook_ident(core)
                                 })
                                 .collect(),
-                                Box::new(t) as Box<dyn Send + Sync + FnMut() -> Result<MoveResult, Box<dyn Error>>>
+                                t
                             )
                         },
                     )*
@@ -470,6 +724,14 @@ macro_rules! generate_rulebook {
                 }
             }
 
+            fn wall_owners(&self) -> &std::collections::HashMap<Wall, PlayerID> {
+                match self {
+                    $(
+                        Self::$rulebook_ident(g) => &g.wall_owners,
+                    )*
+                }
+            }
+
             fn turn_of(&self) -> PlayerID {
                 match self {
                     $(
@@ -0,0 +1,141 @@
+use crate::rulebooks::*;
+use crate::*;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MovingWallsQuoridor;
+
+fn check_owned(game: &QGame<MovingWallsQuoridor>, wall: &Wall) -> Result<(), ()> {
+    match game.wall_owners.get(wall) {
+        Some(&owner) if owner == game.turn_of => Ok(()),
+        _ => Err(()),
+    }
+}
+
+impl Rulebook for MovingWallsQuoridor {
+    const PLAYER_COUNT: u8 = 2;
+    const PAWN_COUNT: u8 = 2;
+    const MOVABLE_WALLS: bool = true;
+    type Move = Move;
+    type Metadata = ();
+
+    fn validate_move(game: &QGame<Self>, qmove: Move) -> Result<(), ()> {
+        match qmove {
+            Move::PlaceWall(wall) => {
+                if game.wall_counts[game.turn_of as usize] == 0 {
+                    Err(())
+                } else {
+                    check_wall_placement(game, wall)
+                }
+            }
+            Move::MovePawn(start_pos, target_pos) => check_pawn_move(game, start_pos, target_pos),
+            Move::RemoveWall(wall) => check_owned(game, &wall),
+            Move::MoveWall(start, end) => {
+                // Putting a wall back where it was would be a free pass
+                if start == end {
+                    return Err(());
+                }
+                check_owned(game, &start)?;
+                let mut hypothetical = Clone::clone(game);
+                hypothetical.walls.remove(&start);
+                check_wall_placement(&hypothetical, end)
+            }
+        }
+    }
+
+    fn apply_move(game: &mut QGame<Self>, qmove: Move) -> MoveResult {
+        match qmove {
+            Move::RemoveWall(wall) => {
+                game.walls.remove(&wall);
+                game.wall_owners.remove(&wall);
+                game.wall_counts[game.turn_of as usize] += 1;
+            }
+            Move::MoveWall(start, end) => {
+                game.walls.remove(&start);
+                game.wall_owners.remove(&start);
+                game.walls.insert(end);
+                game.wall_owners.insert(end, game.turn_of);
+            }
+            _ => apply_standard_move(game, qmove),
+        }
+        finish_standard_turn(game)
+    }
+
+    fn initial_server() -> QGame<Self> {
+        let mut pawns = BiMap::with_capacity(2);
+        pawns.insert(0, Position::from((4, 0)));
+        pawns.insert(1, Position::from((4, 8)));
+        QGame::<Self> {
+            wall_counts: vec![10, 10],
+            pawn_positions: pawns,
+            walls: HashSet::new(),
+            wall_owners: HashMap::new(),
+            turn_of: 0,
+            metadata: (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_walls(walls: &[(Wall, PlayerID)]) -> QGame<MovingWallsQuoridor> {
+        let mut game = MovingWallsQuoridor::initial_server();
+        for &(wall, owner) in walls {
+            game.walls.insert(wall);
+            game.wall_owners.insert(wall, owner);
+            game.wall_counts[owner as usize] -= 1;
+        }
+        game
+    }
+
+    #[test]
+    fn only_own_walls_can_be_moved() {
+        let mine = Wall::horizontal((2, 4).into());
+        let theirs = Wall::horizontal((6, 4).into());
+        let game = with_walls(&[(mine, 0), (theirs, 1)]);
+        let to = Wall::horizontal((2, 6).into());
+
+        assert!(MovingWallsQuoridor::validate_move(&game, Move::MoveWall(mine, to)).is_ok());
+        assert!(MovingWallsQuoridor::validate_move(&game, Move::RemoveWall(mine)).is_ok());
+        assert!(MovingWallsQuoridor::validate_move(&game, Move::MoveWall(theirs, to)).is_err());
+        assert!(MovingWallsQuoridor::validate_move(&game, Move::RemoveWall(theirs)).is_err());
+    }
+
+    #[test]
+    fn walls_must_move_somewhere() {
+        let wall = Wall::horizontal((2, 4).into());
+        let game = with_walls(&[(wall, 0)]);
+        assert!(MovingWallsQuoridor::validate_move(&game, Move::MoveWall(wall, wall)).is_err());
+    }
+
+    #[test]
+    fn moved_walls_must_leave_a_path() {
+        // Player 0's row is closed off above columns 1 to 8, so a wall between columns 0 and 1 shuts them in
+        let mut walls = (1..5)
+            .map(|i| (Wall::horizontal((2 * i, 1).into()), 0))
+            .collect::<Vec<_>>();
+        let spare = Wall::vertical((1, 5).into());
+        walls.push((spare, 0));
+        let game = with_walls(&walls);
+
+        let closing = Move::MoveWall(spare, Wall::vertical((1, 1).into()));
+        assert!(MovingWallsQuoridor::validate_move(&game, closing).is_err());
+        let open = with_walls(&[(spare, 0)]);
+        assert!(MovingWallsQuoridor::validate_move(&open, closing).is_ok());
+        let harmless = Move::MoveWall(spare, Wall::vertical((1, 3).into()));
+        assert!(MovingWallsQuoridor::validate_move(&game, harmless).is_ok());
+    }
+
+    #[test]
+    fn removed_walls_return_to_the_supply() {
+        let wall = Wall::horizontal((2, 4).into());
+        let mut game = with_walls(&[(wall, 0)]);
+        assert_eq!(game.wall_counts, vec![9, 10]);
+
+        MovingWallsQuoridor::apply_move(&mut game, Move::RemoveWall(wall));
+        assert_eq!(game.wall_counts, vec![10, 10]);
+        assert!(!game.walls.contains(&wall));
+        assert!(game.wall_owners.get(&wall).is_none());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::rng::SplitMix64;
+use crate::rulebooks::*;
+use crate::*;
+
+const MAX_NEUTRAL_WALL_PAIRS: u64 = 3;
+const MAX_COLUMN_SHIFT: u64 = 2;
+const PLACEMENT_ATTEMPTS: u32 = 64;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RandomSetupQuoridor;
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RandomSetupMetadata {
+    pub seed: u64,
+}
+
+impl RandomSetupQuoridor {
+    pub fn from_seed(seed: u64) -> QGame<Self> {
+        let mut rng = SplitMix64::new(seed);
+
+        let shift = rng.below(MAX_COLUMN_SHIFT * 2 + 1) as u8;
+        let column = 4 + shift - MAX_COLUMN_SHIFT as u8;
+
+        let mut pawns = BiMap::with_capacity(2);
+        pawns.insert(0, Position::from((column, 0)));
+        pawns.insert(1, Position::from((8 - column, 8)));
+        let mut game = QGame::<Self> {
+            wall_counts: vec![10, 10],
+            pawn_positions: pawns,
+            walls: HashSet::new(),
+            wall_owners: HashMap::new(),
+            turn_of: 0,
+            metadata: RandomSetupMetadata { seed },
+        };
+
+        let pairs = rng.below(MAX_NEUTRAL_WALL_PAIRS + 1);
+        let mut placed = 0;
+        let mut attempts = 0;
+        while placed < pairs && attempts < PLACEMENT_ATTEMPTS {
+            attempts += 1;
+
+            let position = Position::from((rng.below(8) as u8 + 1, rng.below(8) as u8 + 1));
+            let wall = if rng.below(2) == 0 {
+                Wall::horizontal(position)
+            } else {
+                Wall::vertical(position)
+            };
+            // Rotating the board by 180 degrees maps wall (x, y) to (9 - x, 9 - y)
+            let mirrored = Wall {
+                position: Position::from((9 - position.x, 9 - position.y)),
+                ..wall
+            };
+
+            if check_wall_placement(&game, wall).is_err() {
+                continue;
+            }
+            let mut hypothetical = Clone::clone(&game);
+            hypothetical.walls.insert(wall);
+            if check_wall_placement(&hypothetical, mirrored).is_err() {
+                continue;
+            }
+
+            game.walls.insert(wall);
+            game.walls.insert(mirrored);
+            placed += 1;
+        }
+
+        game
+    }
+}
+
+impl Rulebook for RandomSetupQuoridor {
+    const PLAYER_COUNT: u8 = 2;
+    const PAWN_COUNT: u8 = 2;
+    type Move = Move;
+    type Metadata = RandomSetupMetadata;
+
+    fn validate_move(game: &QGame<Self>, qmove: Move) -> Result<(), ()> {
+        match qmove {
+            Move::PlaceWall(wall) => {
+                if game.wall_counts[game.turn_of as usize] == 0 {
+                    Err(())
+                } else {
+                    check_wall_placement(game, wall)
+                }
+            }
+            Move::MovePawn(start_pos, target_pos) => check_pawn_move(game, start_pos, target_pos),
+            Move::RemoveWall(_) => Err(()),
+            Move::MoveWall(_, _) => Err(()),
+        }
+    }
+
+    fn apply_move(game: &mut QGame<Self>, qmove: Move) -> MoveResult {
+        apply_standard_move(game, qmove);
+        finish_standard_turn(game)
+    }
+
+    fn initial_server() -> QGame<Self> {
+        Self::from_seed(crate::rng::time_seed())
+    }
+
+    fn set_option(game: &mut QGame<Self>, key: &str, value: &str) -> Result<(), ()> {
+        match key {
+            "seed" => *game = Self::from_seed(value.parse().map_err(|_| ())?),
+            _ => return Err(()),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setup_is_mirrored() {
+        for seed in 0..200 {
+            let game = RandomSetupQuoridor::from_seed(seed);
+            let p0 = *game.pawn_positions.get_by_left(&0).unwrap();
+            let p1 = *game.pawn_positions.get_by_left(&1).unwrap();
+            assert_eq!((p0.y, p1.y), (0, 8));
+            assert_eq!(p1.x, 8 - p0.x);
+            for wall in game.walls.iter() {
+                let mirrored = Wall {
+                    position: (9 - wall.position.x, 9 - wall.position.y).into(),
+                    ..*wall
+                };
+                assert!(game.walls.contains(&mirrored), "seed {}: {:?}", seed, wall);
+            }
+            assert!(game.wall_owners.is_empty());
+            assert!(check_paths(&game).is_ok());
+        }
+    }
+
+    #[test]
+    fn same_seed_same_setup() {
+        let (a, b) = (
+            RandomSetupQuoridor::from_seed(42),
+            RandomSetupQuoridor::from_seed(42),
+        );
+        assert_eq!(a.pawn_positions, b.pawn_positions);
+        assert_eq!(a.walls, b.walls);
+        assert_eq!(a.metadata.seed, 42);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::rulebooks::*;
+use crate::*;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+pub trait BoardSize: Send + Sync + Clone + Debug + 'static {
+    const SIZE: u8;
+    const WALLS: u8;
+}
+
+#[derive(Clone, Debug)]
+pub struct Board3;
+impl BoardSize for Board3 {
+    const SIZE: u8 = 3;
+    const WALLS: u8 = 1;
+}
+
+#[derive(Clone, Debug)]
+pub struct Board4;
+impl BoardSize for Board4 {
+    const SIZE: u8 = 4;
+    const WALLS: u8 = 2;
+}
+
+#[derive(Clone, Debug)]
+pub struct Board5;
+impl BoardSize for Board5 {
+    const SIZE: u8 = 5;
+    const WALLS: u8 = 3;
+}
+
+// Standard rules on a smaller board, mostly useful for solving exactly
+#[derive(Clone, Debug)]
+pub struct SizedQuoridor<S: BoardSize>(PhantomData<S>);
+
+pub type Quoridor3 = SizedQuoridor<Board3>;
+pub type Quoridor4 = SizedQuoridor<Board4>;
+pub type Quoridor5 = SizedQuoridor<Board5>;
+
+impl<S: BoardSize> Rulebook for SizedQuoridor<S> {
+    const PLAYER_COUNT: u8 = 2;
+    const PAWN_COUNT: u8 = 2;
+    const BOARD_SIZE: u8 = S::SIZE;
+    type Move = Move;
+    type Metadata = ();
+
+    fn validate_move(game: &QGame<Self>, qmove: Move) -> Result<(), ()> {
+        match qmove {
+            Move::PlaceWall(wall) => {
+                if game.wall_counts[game.turn_of as usize] == 0 {
+                    Err(())
+                } else {
+                    check_wall_placement(game, wall)
+                }
+            }
+            Move::MovePawn(start_pos, target_pos) => check_pawn_move(game, start_pos, target_pos),
+            Move::RemoveWall(_) => Err(()),
+            Move::MoveWall(_, _) => Err(()),
+        }
+    }
+
+    fn apply_move(game: &mut QGame<Self>, qmove: Move) -> MoveResult {
+        apply_standard_move(game, qmove);
+        finish_standard_turn(game)
+    }
+
+    fn initial_server() -> QGame<Self> {
+        let mut pawns = BiMap::with_capacity(2);
+        pawns.insert(0, Position::from((S::SIZE / 2, 0)));
+        pawns.insert(1, Position::from((S::SIZE / 2, S::SIZE - 1)));
+        QGame::<Self> {
+            wall_counts: vec![S::WALLS, S::WALLS],
+            pawn_positions: pawns,
+            walls: HashSet::new(),
+            wall_owners: HashMap::new(),
+            turn_of: 0,
+            metadata: (),
+        }
+    }
+}
@@ -3,12 +3,13 @@ use crate::*;
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct StandardQuoridor;
 
-fn check_movable(
-    game: &QGame<StandardQuoridor>,
+pub(crate) fn check_movable<Rb: Rulebook>(
+    game: &QGame<Rb>,
     pawn_pos: Position,
     pos: Position,
     check_jump: bool,
 ) -> Result<(), ()> {
+    let last = Rb::BOARD_SIZE - 1;
     let x = pawn_pos.x as i8 - pos.x as i8;
     let y = pawn_pos.y as i8 - pos.y as i8;
 
@@ -55,11 +56,11 @@ fn check_movable(
                 Err(())
             }
         } else {
-            if pawn_pos.x != 8
+            if pawn_pos.x != last
                 && game
                     .pawn_positions
                     .contains_right(&Position::from((pawn_pos.x + 1, pawn_pos.y)))
-                && (pawn_pos.x == 7
+                && (pawn_pos.x == last - 1
                     || check_movable(
                         game,
                         (pawn_pos.x + 1, pawn_pos.y).into(),
@@ -100,11 +101,11 @@ fn check_movable(
                 && check_movable(game, (pawn_pos.x, pawn_pos.y - 1).into(), pos, false).is_ok()
             {
                 Ok(())
-            } else if pawn_pos.y != 8
+            } else if pawn_pos.y != last
                 && game
                     .pawn_positions
                     .contains_right(&Position::from((pawn_pos.x, pawn_pos.y + 1)))
-                && (pawn_pos.y == 7
+                && (pawn_pos.y == last - 1
                     || check_movable(
                         game,
                         (pawn_pos.x, pawn_pos.y + 1).into(),
@@ -124,12 +125,13 @@ fn check_movable(
     }
 }
 
-fn check_reach(
-    game: &QGame<StandardQuoridor>,
+pub(crate) fn check_reach<Rb: Rulebook>(
+    game: &QGame<Rb>,
     current_pos: Position,
     target_pos: Position,
     visited: &mut HashSet<Position>,
 ) -> Result<(), ()> {
+    let last = Rb::BOARD_SIZE - 1;
     visited.insert(current_pos);
     //println!("visiting {:?}", current_pos);
     //println!("visited: {:?}", visited);
@@ -157,7 +159,7 @@ fn check_reach(
         }
     }
 
-    if current_pos.x != 8
+    if current_pos.x != last
         && !visited.contains(&(current_pos.x + 1, current_pos.y).into())
         && check_movable(
             game,
@@ -197,7 +199,7 @@ fn check_reach(
         }
     }
 
-    if current_pos.y != 8
+    if current_pos.y != last
         && !visited.contains(&(current_pos.x, current_pos.y + 1).into())
         && check_movable(
             game,
@@ -220,6 +222,138 @@ fn check_reach(
     Err(())
 }
 
+// Whether the wall would cross a wall at the same point or run into one next to it
+fn crosses_neighbour<Rb: Rulebook>(game: &QGame<Rb>, wall: Wall) -> bool {
+    let pos = wall.position;
+    match wall.orientation {
+        Orientation::Horizontal => {
+            game.walls.contains(&Wall::vertical(pos))
+                || game
+                    .walls
+                    .contains(&Wall::horizontal((pos.x.wrapping_sub(1), pos.y).into()))
+                || game
+                    .walls
+                    .contains(&Wall::horizontal((pos.x + 1, pos.y).into()))
+        }
+        Orientation::Vertical => {
+            game.walls.contains(&Wall::horizontal(pos))
+                || game
+                    .walls
+                    .contains(&Wall::vertical((pos.x, pos.y.wrapping_sub(1)).into()))
+                || game
+                    .walls
+                    .contains(&Wall::vertical((pos.x, pos.y + 1).into()))
+        }
+    }
+}
+
+// The wall rules of the standard rulebook, unchanged from before the other rulebooks existed: a
+// wall already standing may be placed again, and some column must stay reachable by both pawns
+pub(crate) fn check_standard_wall_placement<Rb: Rulebook>(
+    game: &QGame<Rb>,
+    wall: Wall,
+) -> Result<(), ()> {
+    let pos = wall.position;
+    if pos.x == 0 || pos.x == Rb::BOARD_SIZE || pos.y == 0 || pos.y == Rb::BOARD_SIZE {
+        return Err(());
+    }
+    if crosses_neighbour(game, wall) {
+        return Err(());
+    }
+
+    let mut hypothetical = Clone::clone(game);
+    hypothetical.walls.insert(wall);
+    let last = Rb::BOARD_SIZE - 1;
+    let start = |id: PawnID| *game.pawn_positions.get_by_left(&id).unwrap();
+    (0u8..Rb::BOARD_SIZE)
+        .find(|x| {
+            check_reach(
+                &hypothetical,
+                start(0),
+                (*x, last).into(),
+                &mut HashSet::new(),
+            )
+            .is_ok()
+                && check_reach(&hypothetical, start(1), (*x, 0).into(), &mut HashSet::new()).is_ok()
+        })
+        .map(|_| ())
+        .ok_or(())
+}
+
+// The wall rules the newer rulebooks share: no wall twice in the same place, and each pawn
+// only needs some way to its own goal row
+pub(crate) fn check_wall_placement<Rb: Rulebook>(game: &QGame<Rb>, wall: Wall) -> Result<(), ()> {
+    let pos = wall.position;
+    if pos.x == 0 || pos.x >= Rb::BOARD_SIZE || pos.y == 0 || pos.y >= Rb::BOARD_SIZE {
+        return Err(());
+    }
+    if game.walls.contains(&wall) || crosses_neighbour(game, wall) {
+        return Err(());
+    }
+
+    let mut hypothetical = Clone::clone(game);
+    hypothetical.walls.insert(wall);
+    check_paths(&hypothetical)
+}
+
+pub(crate) fn check_paths<Rb: Rulebook>(game: &QGame<Rb>) -> Result<(), ()> {
+    let reaches = |id: PawnID, goal_y: u8| {
+        let start = *game.pawn_positions.get_by_left(&id).unwrap();
+        (0u8..Rb::BOARD_SIZE)
+            .any(|x| check_reach(game, start, (x, goal_y).into(), &mut HashSet::new()).is_ok())
+    };
+
+    if reaches(0, Rb::BOARD_SIZE - 1) && reaches(1, 0) {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
+
+pub(crate) fn check_pawn_move<Rb: Rulebook>(
+    game: &QGame<Rb>,
+    start_pos: Position,
+    target_pos: Position,
+) -> Result<(), ()> {
+    check_movable(game, start_pos, target_pos, true)?;
+    if game.pawn_positions.contains_right(&target_pos) {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn apply_standard_move<Rb: Rulebook>(game: &mut QGame<Rb>, qmove: Move) {
+    match qmove {
+        Move::PlaceWall(wall) => {
+            game.wall_counts[game.turn_of as usize] -= 1;
+            game.walls.insert(wall);
+            game.wall_owners.insert(wall, game.turn_of);
+        }
+        Move::MovePawn(start_pos, end_pos) => {
+            let id = game.pawn_positions.remove_by_right(&start_pos).unwrap().0;
+            game.pawn_positions.insert(id, end_pos);
+        }
+        Move::RemoveWall(_) => {}
+        Move::MoveWall(_, _) => {}
+    }
+}
+
+pub(crate) fn finish_standard_turn<Rb: Rulebook>(game: &mut QGame<Rb>) -> MoveResult {
+    game.turn_of += 1;
+    if game.turn_of == Rb::PLAYER_COUNT {
+        game.turn_of = 0;
+    }
+
+    if game.pawn_positions.get_by_left(&0u8).unwrap().clone().y == Rb::BOARD_SIZE - 1 {
+        MoveResult::Win(0)
+    } else if game.pawn_positions.get_by_left(&1u8).unwrap().clone().y == 0 {
+        MoveResult::Win(1)
+    } else {
+        MoveResult::Continue
+    }
+}
+
 impl Rulebook for StandardQuoridor {
     const PLAYER_COUNT: u8 = 2;
     const PAWN_COUNT: u8 = 2;
@@ -232,150 +366,18 @@ impl Rulebook for StandardQuoridor {
                 if game.wall_counts[game.turn_of as usize] == 0 {
                     Err(())
                 } else {
-                    match wall {
-                        Wall {
-                            orientation: Orientation::Horizontal,
-                            position: pos,
-                            ..
-                        } => {
-                            if pos.x == 0 || pos.x == 9 || pos.y == 0 || pos.y == 9 {
-                                Err(())
-                            } else {
-                                if !game.walls.contains(&Wall::vertical(pos))
-                                    && !game.walls.contains(&Wall::horizontal(
-                                        (pos.x.wrapping_sub(1), pos.y).into(),
-                                    ))
-                                    && !game.walls.contains(&Wall::horizontal(Position::from((
-                                        pos.x + 1,
-                                        pos.y,
-                                    ))))
-                                {
-                                    let mut hypothetical = Clone::clone(game);
-                                    hypothetical.walls.insert(wall);
-                                    (0u8..9)
-                                        .find(|x| {
-                                            check_reach(
-                                                &hypothetical,
-                                                game.pawn_positions
-                                                    .get_by_left(&0u8)
-                                                    .unwrap()
-                                                    .clone(),
-                                                (*x, 8).into(),
-                                                &mut HashSet::new(),
-                                            )
-                                            .is_ok()
-                                                && check_reach(
-                                                    &hypothetical,
-                                                    game.pawn_positions
-                                                        .get_by_left(&1u8)
-                                                        .unwrap()
-                                                        .clone(),
-                                                    (*x, 0).into(),
-                                                    &mut HashSet::new(),
-                                                )
-                                                .is_ok()
-                                        })
-                                        .map(|_| ())
-                                        .ok_or(())
-                                } else {
-                                    Err(())
-                                }
-                            }
-                        }
-                        Wall {
-                            orientation: Orientation::Vertical,
-                            position: pos,
-                            ..
-                        } => {
-                            if pos.x == 0 || pos.x == 9 || pos.y == 0 || pos.y == 9 {
-                                Err(())
-                            } else {
-                                if !game.walls.contains(&Wall::horizontal(pos))
-                                    && !game.walls.contains(&Wall::vertical(Position::from((
-                                        pos.x,
-                                        pos.y.wrapping_sub(1),
-                                    ))))
-                                    && !game.walls.contains(&Wall::vertical(Position::from((
-                                        pos.x,
-                                        pos.y + 1,
-                                    ))))
-                                {
-                                    let mut hypothetical = Clone::clone(game);
-                                    hypothetical.walls.insert(wall);
-                                    (0u8..9)
-                                        .find(|x| {
-                                            check_reach(
-                                                &hypothetical,
-                                                game.pawn_positions
-                                                    .get_by_left(&0u8)
-                                                    .unwrap()
-                                                    .clone(),
-                                                (*x, 8).into(),
-                                                &mut HashSet::new(),
-                                            )
-                                            .is_ok()
-                                                && check_reach(
-                                                    &hypothetical,
-                                                    game.pawn_positions
-                                                        .get_by_left(&1u8)
-                                                        .unwrap()
-                                                        .clone(),
-                                                    (*x, 0).into(),
-                                                    &mut HashSet::new(),
-                                                )
-                                                .is_ok()
-                                        })
-                                        .map(|_| ())
-                                        .ok_or(())
-                                } else {
-                                    Err(())
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            Move::MovePawn(start_pos, target_pos) => {
-                if let Ok(_) = check_movable(game, start_pos, target_pos, true) {
-                    if game.pawn_positions.contains_right(&target_pos) {
-                        Err(())
-                    } else {
-                        Ok(())
-                    }
-                } else {
-                    Err(())
+                    check_standard_wall_placement(game, wall)
                 }
             }
+            Move::MovePawn(start_pos, target_pos) => check_pawn_move(game, start_pos, target_pos),
             Move::RemoveWall(_) => Err(()),
             Move::MoveWall(_, _) => Err(()),
         }
     }
 
     fn apply_move(game: &mut QGame<Self>, qmove: Move) -> MoveResult {
-        match qmove {
-            Move::PlaceWall(wall) => {
-                game.wall_counts[game.turn_of as usize] -= 1;
-                game.walls.insert(wall);
-            }
-            Move::MovePawn(start_pos, end_pos) => {
-                let id = game.pawn_positions.remove_by_right(&start_pos).unwrap().0;
-                game.pawn_positions.insert(id, end_pos);
-            }
-            Move::RemoveWall(_) => {}
-            Move::MoveWall(_, _) => {}
-        }
-        game.turn_of += 1;
-        if game.turn_of == Self::PLAYER_COUNT {
-            game.turn_of = 0;
-        }
-
-        if game.pawn_positions.get_by_left(&0u8).unwrap().clone().y == 8 {
-            MoveResult::Win(0)
-        } else if game.pawn_positions.get_by_left(&1u8).unwrap().clone().y == 0 {
-            MoveResult::Win(1)
-        } else {
-            MoveResult::Continue
-        }
+        apply_standard_move(game, qmove);
+        finish_standard_turn(game)
     }
 
     fn initial_server() -> QGame<Self> {
@@ -386,8 +388,43 @@ impl Rulebook for StandardQuoridor {
             wall_counts: vec![10, 10],
             pawn_positions: pawns,
             walls: HashSet::new(),
+            wall_owners: HashMap::new(),
             turn_of: 0,
             metadata: (),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rulebooks::MovingWallsQuoridor;
+
+    #[test]
+    fn walls_cannot_be_placed_twice() {
+        let wall = Wall::horizontal((4, 4).into());
+        let mut game = MovingWallsQuoridor::initial_server();
+        MovingWallsQuoridor::apply_move(&mut game, Move::PlaceWall(wall));
+        assert!(MovingWallsQuoridor::validate_move(&game, Move::PlaceWall(wall)).is_err());
+    }
+
+    #[test]
+    fn crossing_walls_are_refused() {
+        let mut game = StandardQuoridor::initial_server();
+        StandardQuoridor::apply_move(&mut game, Move::PlaceWall(Wall::horizontal((4, 4).into())));
+        for wall in [
+            Wall::vertical((4, 4).into()),
+            Wall::horizontal((3, 4).into()),
+            Wall::horizontal((5, 4).into()),
+        ]
+        .iter()
+        {
+            assert!(StandardQuoridor::validate_move(&game, Move::PlaceWall(*wall)).is_err());
+        }
+        assert!(StandardQuoridor::validate_move(
+            &game,
+            Move::PlaceWall(Wall::horizontal((6, 4).into()))
+        )
+        .is_ok());
+    }
+}
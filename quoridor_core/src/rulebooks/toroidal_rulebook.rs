@@ -0,0 +1,270 @@
+use crate::rulebooks::*;
+use crate::*;
+use std::collections::VecDeque;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ToroidalQuoridor;
+
+const DIRECTIONS: [(i8, i8); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+fn wrap_x(x: i8) -> u8 {
+    ((x + 9) % 9) as u8
+}
+
+fn step(pos: Position, (dx, dy): (i8, i8)) -> Option<Position> {
+    let y = pos.y as i8 + dy;
+    if y < 0 || y > 8 {
+        None
+    } else {
+        Some(Position::from((wrap_x(pos.x as i8 + dx), y as u8)))
+    }
+}
+
+fn blocked<Rb: Rulebook>(game: &QGame<Rb>, pos: Position, (dx, dy): (i8, i8)) -> bool {
+    if dx != 0 {
+        // The blocking vertical wall sits on the column boundary to the right of the left cell
+        let x = if dx > 0 {
+            wrap_x(pos.x as i8 + 1)
+        } else {
+            pos.x
+        };
+        game.walls.contains(&Wall::vertical((x, pos.y).into()))
+            || game.walls.contains(&Wall::vertical((x, pos.y + 1).into()))
+    } else {
+        let y = if dy > 0 { pos.y + 1 } else { pos.y };
+        game.walls.contains(&Wall::horizontal((pos.x, y).into()))
+            || game
+                .walls
+                .contains(&Wall::horizontal((wrap_x(pos.x as i8 + 1), y).into()))
+    }
+}
+
+fn neighbours(game: &QGame<ToroidalQuoridor>, pos: Position) -> Vec<(Position, (i8, i8))> {
+    toroidal_neighbours(game, pos)
+}
+
+// Generic so path searches outside the rulebook, such as the bots', can follow the seam too
+pub(crate) fn toroidal_neighbours<Rb: Rulebook>(
+    game: &QGame<Rb>,
+    pos: Position,
+) -> Vec<(Position, (i8, i8))> {
+    DIRECTIONS
+        .iter()
+        .filter(|&&dir| !blocked(game, pos, dir))
+        .filter_map(|&dir| step(pos, dir).map(|p| (p, dir)))
+        .collect()
+}
+
+pub fn toroidal_pawn_targets(game: &QGame<ToroidalQuoridor>, pawn_pos: Position) -> Vec<Position> {
+    let mut targets = vec![];
+    for (next, dir) in neighbours(game, pawn_pos) {
+        if !game.pawn_positions.contains_right(&next) {
+            targets.push(next);
+            continue;
+        }
+
+        let straight = step(next, dir).filter(|_| !blocked(game, next, dir));
+        match straight {
+            Some(jump) => {
+                if !game.pawn_positions.contains_right(&jump) {
+                    targets.push(jump);
+                }
+            }
+            None => {
+                for (side, side_dir) in neighbours(game, next) {
+                    if side_dir != dir
+                        && side != pawn_pos
+                        && !game.pawn_positions.contains_right(&side)
+                    {
+                        targets.push(side);
+                    }
+                }
+            }
+        }
+    }
+    targets
+}
+
+fn reaches_row(game: &QGame<ToroidalQuoridor>, start: Position, goal_y: u8) -> bool {
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(start);
+    queue.push_back(start);
+    while let Some(pos) = queue.pop_front() {
+        if pos.y == goal_y {
+            return true;
+        }
+        for (next, _) in neighbours(game, pos) {
+            if visited.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    false
+}
+
+fn check_toroidal_wall(game: &QGame<ToroidalQuoridor>, wall: Wall) -> Result<(), ()> {
+    let pos = wall.position;
+    if pos.x > 8 || pos.y == 0 || pos.y == 9 {
+        return Err(());
+    }
+
+    let left = wrap_x(pos.x as i8 - 1);
+    let right = wrap_x(pos.x as i8 + 1);
+    let overlaps = game.walls.contains(&wall)
+        || match wall.orientation {
+            Orientation::Horizontal => {
+                game.walls.contains(&Wall::vertical(pos))
+                    || game.walls.contains(&Wall::horizontal((left, pos.y).into()))
+                    || game.walls.contains(&Wall::horizontal((right, pos.y).into()))
+            }
+            Orientation::Vertical => {
+                game.walls.contains(&Wall::horizontal(pos))
+                    || game
+                        .walls
+                        .contains(&Wall::vertical((pos.x, pos.y - 1).into()))
+                    || game
+                        .walls
+                        .contains(&Wall::vertical((pos.x, pos.y + 1).into()))
+            }
+        };
+
+    if overlaps {
+        return Err(());
+    }
+
+    let mut hypothetical = Clone::clone(game);
+    hypothetical.walls.insert(wall);
+    let p0 = *game.pawn_positions.get_by_left(&0).unwrap();
+    let p1 = *game.pawn_positions.get_by_left(&1).unwrap();
+    if reaches_row(&hypothetical, p0, 8) && reaches_row(&hypothetical, p1, 0) {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
+
+impl Rulebook for ToroidalQuoridor {
+    const PLAYER_COUNT: u8 = 2;
+    const PAWN_COUNT: u8 = 2;
+    const TOROIDAL: bool = true;
+    type Move = Move;
+    type Metadata = ();
+
+    fn validate_move(game: &QGame<Self>, qmove: Move) -> Result<(), ()> {
+        match qmove {
+            Move::PlaceWall(wall) => {
+                if game.wall_counts[game.turn_of as usize] == 0 {
+                    Err(())
+                } else {
+                    check_toroidal_wall(game, wall)
+                }
+            }
+            Move::MovePawn(start_pos, target_pos) => {
+                if toroidal_pawn_targets(game, start_pos).contains(&target_pos) {
+                    Ok(())
+                } else {
+                    Err(())
+                }
+            }
+            Move::RemoveWall(_) => Err(()),
+            Move::MoveWall(_, _) => Err(()),
+        }
+    }
+
+    fn apply_move(game: &mut QGame<Self>, qmove: Move) -> MoveResult {
+        apply_standard_move(game, qmove);
+        finish_standard_turn(game)
+    }
+
+    fn initial_server() -> QGame<Self> {
+        let mut pawns = BiMap::with_capacity(2);
+        pawns.insert(0, Position::from((4, 0)));
+        pawns.insert(1, Position::from((4, 8)));
+        QGame::<Self> {
+            wall_counts: vec![10, 10],
+            pawn_positions: pawns,
+            walls: HashSet::new(),
+            wall_owners: HashMap::new(),
+            turn_of: 0,
+            metadata: (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_pawns(p0: (u8, u8), p1: (u8, u8)) -> QGame<ToroidalQuoridor> {
+        let mut game = ToroidalQuoridor::initial_server();
+        game.pawn_positions.clear();
+        game.pawn_positions.insert(0, p0.into());
+        game.pawn_positions.insert(1, p1.into());
+        game
+    }
+
+    fn pawn_move(game: &QGame<ToroidalQuoridor>, from: (u8, u8), to: (u8, u8)) -> Result<(), ()> {
+        ToroidalQuoridor::validate_move(game, Move::MovePawn(from.into(), to.into()))
+    }
+
+    #[test]
+    fn bots_measure_paths_across_the_seam() {
+        let mut game = with_pawns((0, 4), (4, 8));
+        // Only column 8 is open above row 4, one step away across the seam
+        for x in [1, 3, 5, 7].iter() {
+            game.walls.insert(Wall::horizontal((*x, 5).into()));
+        }
+        assert_eq!(crate::ai::shortest_path(&game, (0, 4).into(), 8), Some(5));
+    }
+
+    #[test]
+    fn pawns_wrap_around() {
+        let game = with_pawns((0, 4), (4, 8));
+        assert!(pawn_move(&game, (0, 4), (8, 4)).is_ok());
+        assert!(pawn_move(&game, (0, 4), (1, 4)).is_ok());
+
+        let game = with_pawns((8, 4), (4, 8));
+        assert!(pawn_move(&game, (8, 4), (0, 4)).is_ok());
+    }
+
+    #[test]
+    fn jumps_wrap_around() {
+        let game = with_pawns((8, 4), (0, 4));
+        assert!(pawn_move(&game, (8, 4), (1, 4)).is_ok());
+        assert!(pawn_move(&game, (8, 4), (0, 4)).is_err());
+    }
+
+    #[test]
+    fn vertical_wall_on_the_seam() {
+        let mut game = with_pawns((0, 4), (4, 8));
+        // x = 0 is off the board in the standard rulebook, but here it is the boundary between columns 8 and 0
+        let wall = Wall::vertical((0, 4).into());
+        assert!(ToroidalQuoridor::validate_move(&game, Move::PlaceWall(wall)).is_ok());
+        game.walls.insert(wall);
+        assert!(pawn_move(&game, (0, 4), (8, 4)).is_err());
+        assert!(pawn_move(&game, (0, 4), (1, 4)).is_ok());
+    }
+
+    #[test]
+    fn horizontal_wall_across_the_seam() {
+        let mut game = with_pawns((8, 4), (4, 8));
+        let wall = Wall::horizontal((0, 5).into());
+        assert!(ToroidalQuoridor::validate_move(&game, Move::PlaceWall(wall)).is_ok());
+        game.walls.insert(wall);
+        // The wall covers columns 8 and 0
+        assert!(pawn_move(&game, (8, 4), (8, 5)).is_err());
+        let game_on_zero = {
+            let mut g = game.clone();
+            g.pawn_positions.insert(0, (0, 4).into());
+            g
+        };
+        assert!(pawn_move(&game_on_zero, (0, 4), (0, 5)).is_err());
+
+        // Walls running into it from either side of the seam overlap it
+        for x in [8, 1].iter() {
+            let wall = Wall::horizontal((*x, 5).into());
+            assert!(ToroidalQuoridor::validate_move(&game, Move::PlaceWall(wall)).is_err());
+        }
+    }
+}
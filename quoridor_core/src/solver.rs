@@ -0,0 +1,350 @@
+use crate::ai::legal_moves;
+use crate::*;
+use std::collections::VecDeque;
+
+// Seen from the side to move, with the number of plies until the game ends under perfect play
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Value {
+    Win(u16),
+    Loss(u16),
+    Draw,
+}
+
+impl Value {
+    // The value for the player who made the move leading to a position of this value
+    pub fn for_mover(self) -> Value {
+        match self {
+            Value::Win(d) => Value::Loss(d + 1),
+            Value::Loss(d) => Value::Win(d + 1),
+            Value::Draw => Value::Draw,
+        }
+    }
+
+    // Faster wins and slower losses rank higher
+    pub fn rank(self) -> i32 {
+        match self {
+            Value::Win(d) => 100_000 - d as i32,
+            Value::Draw => 0,
+            Value::Loss(d) => -100_000 + d as i32,
+        }
+    }
+}
+
+pub const MAX_SOLVER_BOARD_SIZE: u8 = 5;
+
+fn wall_bit<Rb: Rulebook>(wall: Wall) -> u64 {
+    let inner = Rb::BOARD_SIZE as u64 - 1;
+    let index = (wall.position.y as u64 - 1) * inner + (wall.position.x as u64 - 1);
+    1 << (index * 2 + (wall.orientation == Orientation::Vertical) as u64)
+}
+
+// Walls take the low 32 bits, followed by both pawns, the wall counts and the side to move
+pub fn encode<Rb: Rulebook>(game: &QGame<Rb>) -> u64 {
+    let size = Rb::BOARD_SIZE as u64;
+    let cell = |id: PawnID| {
+        let pos = game.pawn_positions.get_by_left(&id).unwrap();
+        pos.y as u64 * size + pos.x as u64
+    };
+
+    let walls = game
+        .walls
+        .iter()
+        .fold(0, |bits, &wall| bits | wall_bit::<Rb>(wall));
+    walls
+        | cell(0) << 32
+        | cell(1) << 37
+        | (game.wall_counts[0] as u64) << 42
+        | (game.wall_counts[1] as u64) << 46
+        | (game.turn_of as u64) << 50
+}
+
+pub fn decode<Rb: Rulebook>(key: u64) -> QGame<Rb> {
+    let size = Rb::BOARD_SIZE as u64;
+    let inner = size - 1;
+    let cell = |bits: u64| Position::from(((bits % size) as u8, (bits / size) as u8));
+
+    let mut game = Rb::initial_server();
+    game.pawn_positions.clear();
+    game.pawn_positions.insert(0, cell(key >> 32 & 0x1f));
+    game.pawn_positions.insert(1, cell(key >> 37 & 0x1f));
+    game.wall_counts = vec![(key >> 42 & 0xf) as u8, (key >> 46 & 0xf) as u8];
+    game.turn_of = (key >> 50 & 1) as PlayerID;
+    game.walls.clear();
+    game.wall_owners.clear();
+    for bit in 0..inner * inner * 2 {
+        if key & 1 << bit != 0 {
+            let index = bit / 2;
+            let position = Position::from(((index % inner) as u8 + 1, (index / inner) as u8 + 1));
+            game.walls.insert(if bit % 2 == 1 {
+                Wall::vertical(position)
+            } else {
+                Wall::horizontal(position)
+            });
+        }
+    }
+    game
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SolutionTable {
+    pub board_size: u8,
+    pub values: HashMap<u64, Value>,
+}
+
+impl SolutionTable {
+    pub fn value<Rb: Rulebook>(&self, game: &QGame<Rb>) -> Option<Value> {
+        self.values.get(&encode(game)).copied()
+    }
+
+    // The value of a move for the player making it
+    pub fn move_value<Rb: Rulebook<Move = Move>>(&self, game: &QGame<Rb>, qmove: Move) -> Option<Value> {
+        let mut child = Clone::clone(game);
+        match Rb::apply_move(&mut child, qmove) {
+            MoveResult::Win(id) if id == game.turn_of => Some(Value::Win(1)),
+            _ => self.value(&child).map(Value::for_mover),
+        }
+    }
+
+    pub fn best_moves<Rb: Rulebook<Move = Move>>(&self, game: &QGame<Rb>) -> Vec<Move> {
+        let scored = legal_moves(game)
+            .into_iter()
+            .filter_map(|qmove| self.move_value(game, qmove).map(|v| (qmove, v.rank())))
+            .collect::<Vec<_>>();
+        let best = scored.iter().map(|&(_, rank)| rank).max();
+        scored
+            .into_iter()
+            .filter(|&(_, rank)| Some(rank) == best)
+            .map(|(qmove, _)| qmove)
+            .collect()
+    }
+}
+
+pub fn solve<Rb: Rulebook<Move = Move>>() -> Result<SolutionTable, ()> {
+    solve_from(Rb::initial_server())
+}
+
+// Enumerates every position reachable from `initial`, then resolves values backwards from the finished games
+pub fn solve_from<Rb: Rulebook<Move = Move>>(initial: QGame<Rb>) -> Result<SolutionTable, ()> {
+    if Rb::BOARD_SIZE > MAX_SOLVER_BOARD_SIZE || Rb::PAWN_COUNT != 2 || Rb::PLAYER_COUNT != 2 {
+        return Err(());
+    }
+
+    let mut index: HashMap<u64, u32> = HashMap::new();
+    let mut keys = vec![encode(&initial)];
+    let mut finished = vec![false];
+    let mut predecessors: Vec<Vec<u32>> = vec![vec![]];
+    let mut remaining: Vec<u32> = vec![0];
+    index.insert(keys[0], 0);
+
+    let mut frontier = VecDeque::new();
+    frontier.push_back(0u32);
+    while let Some(i) = frontier.pop_front() {
+        if finished[i as usize] {
+            continue;
+        }
+        let game = decode::<Rb>(keys[i as usize]);
+        let moves = legal_moves(&game);
+        remaining[i as usize] = moves.len() as u32;
+
+        for qmove in moves {
+            let mut child = Clone::clone(&game);
+            let result = Rb::apply_move(&mut child, qmove);
+            let key = encode(&child);
+            let j = match index.get(&key) {
+                Some(&j) => j,
+                None => {
+                    let j = keys.len() as u32;
+                    index.insert(key, j);
+                    keys.push(key);
+                    finished.push(matches!(result, MoveResult::Win(_)));
+                    predecessors.push(vec![]);
+                    remaining.push(0);
+                    frontier.push_back(j);
+                    j
+                }
+            };
+            predecessors[j as usize].push(i);
+        }
+    }
+
+    let mut values: Vec<Option<Value>> = vec![None; keys.len()];
+    let mut longest = vec![0u16; keys.len()];
+    let mut queue = VecDeque::new();
+    for (i, &done) in finished.iter().enumerate() {
+        if done {
+            values[i] = Some(Value::Loss(0));
+            queue.push_back(i);
+        }
+    }
+
+    while let Some(j) = queue.pop_front() {
+        let value = values[j].unwrap();
+        for &p in predecessors[j].iter() {
+            let p = p as usize;
+            if values[p].is_some() {
+                continue;
+            }
+            match value {
+                Value::Loss(d) => {
+                    values[p] = Some(Value::Win(d + 1));
+                    queue.push_back(p);
+                }
+                Value::Win(d) => {
+                    remaining[p] -= 1;
+                    longest[p] = longest[p].max(d);
+                    if remaining[p] == 0 {
+                        values[p] = Some(Value::Loss(longest[p] + 1));
+                        queue.push_back(p);
+                    }
+                }
+                Value::Draw => {}
+            }
+        }
+    }
+
+    // Anything never forced either way can be stalled forever
+    Ok(SolutionTable {
+        board_size: Rb::BOARD_SIZE,
+        values: keys
+            .into_iter()
+            .zip(values)
+            .map(|(key, value)| (key, value.unwrap_or(Value::Draw)))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rulebooks::{Quoridor3, Quoridor4};
+
+    // A plain depth-limited search, independent of the table, to check its values against
+    fn search(
+        game: &QGame<Quoridor3>,
+        depth: u16,
+        memo: &mut HashMap<(String, u16), Option<Value>>,
+    ) -> Option<Value> {
+        if depth == 0 {
+            return None;
+        }
+        let mut walls = game
+            .walls
+            .iter()
+            .map(|w| format!("{:?}", w))
+            .collect::<Vec<_>>();
+        walls.sort();
+        let key = (
+            format!(
+                "{:?} {:?} {:?} {:?} {}",
+                game.pawn_positions.get_by_left(&0),
+                game.pawn_positions.get_by_left(&1),
+                walls,
+                game.wall_counts,
+                game.turn_of
+            ),
+            depth,
+        );
+        if let Some(&value) = memo.get(&key) {
+            return value;
+        }
+
+        let mut best_win: Option<u16> = None;
+        let mut longest_loss = Some(0);
+        for qmove in legal_moves(game) {
+            let mut child = Clone::clone(game);
+            let value = match Quoridor3::apply_move(&mut child, qmove) {
+                MoveResult::Win(_) => Some(Value::Win(1)),
+                _ => search(&child, depth - 1, memo).map(Value::for_mover),
+            };
+            match value {
+                Some(Value::Win(d)) => best_win = Some(best_win.map_or(d, |b| b.min(d))),
+                Some(Value::Loss(d)) => longest_loss = longest_loss.map(|l: u16| l.max(d)),
+                _ => longest_loss = None,
+            }
+        }
+        let value = match (best_win, longest_loss) {
+            (Some(d), _) => Some(Value::Win(d)),
+            (None, Some(d)) => Some(Value::Loss(d)),
+            (None, None) => None,
+        };
+        memo.insert(key, value);
+        value
+    }
+
+    fn same_position<Rb: Rulebook>(a: &QGame<Rb>, b: &QGame<Rb>) -> bool {
+        a.pawn_positions == b.pawn_positions
+            && a.walls == b.walls
+            && a.wall_counts == b.wall_counts
+            && a.turn_of == b.turn_of
+    }
+
+    #[test]
+    fn keys_round_trip() {
+        let table = solve::<Quoridor3>().unwrap();
+        for &key in table.values.keys() {
+            assert_eq!(encode(&decode::<Quoridor3>(key)), key);
+        }
+
+        // Walk a few games on a larger board, wall by wall
+        let mut rng = crate::rng::SplitMix64::new(7);
+        for _ in 0..20 {
+            let mut game = Quoridor4::initial_server();
+            for _ in 0..30 {
+                assert!(same_position(&decode::<Quoridor4>(encode(&game)), &game));
+                let moves = legal_moves(&game);
+                let qmove = moves[rng.below(moves.len() as u64) as usize];
+                if !matches!(
+                    Quoridor4::apply_move(&mut game, qmove),
+                    MoveResult::Continue
+                ) {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn small_board_first_player_loses() {
+        let table = solve::<Quoridor3>().unwrap();
+        let initial = Quoridor3::initial_server();
+        assert_eq!(table.value(&initial), Some(Value::Loss(8)));
+
+        let mut memo = HashMap::new();
+        assert_eq!(search(&initial, 8, &mut memo), Some(Value::Loss(8)));
+        assert_eq!(search(&initial, 7, &mut memo), None);
+    }
+
+    #[test]
+    fn one_step_from_goal_wins() {
+        let table = solve::<Quoridor3>().unwrap();
+        let mut game = Quoridor3::initial_server();
+        game.pawn_positions.clear();
+        game.pawn_positions.insert(0, (0, 1).into());
+        game.pawn_positions.insert(1, (2, 1).into());
+        assert_eq!(table.value(&game), Some(Value::Win(1)));
+    }
+
+    #[test]
+    fn best_moves_are_legal_and_keep_the_value() {
+        let table = solve::<Quoridor3>().unwrap();
+        for (&key, &value) in table.values.iter() {
+            if value == Value::Loss(0) {
+                continue;
+            }
+            let game = decode::<Quoridor3>(key);
+            let legal = legal_moves(&game);
+            let best = table.best_moves(&game);
+            // A pawn can be boxed in with no move at all, which the table counts as a draw
+            assert_eq!(best.is_empty(), legal.is_empty());
+            for qmove in best {
+                assert!(legal.contains(&qmove));
+                assert_eq!(table.move_value(&game, qmove), Some(value));
+            }
+            // No legal move does better than the position's value
+            for qmove in legal {
+                let rank = table.move_value(&game, qmove).unwrap().rank();
+                assert!(rank <= value.rank());
+            }
+        }
+    }
+}
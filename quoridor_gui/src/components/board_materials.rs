@@ -6,6 +6,8 @@ pub struct BoardMaterials {
     pub select: Handle<ColorMaterial>,
     pub highlight: Handle<ColorMaterial>,
     pub wall_mat_handle: Handle<ColorMaterial>,
+    pub seam_mat_handle: Handle<ColorMaterial>,
+    pub hint: Handle<ColorMaterial>,
     pub pawn_materials: Vec<Handle<ColorMaterial>>,
 }
 
@@ -20,6 +22,8 @@ impl FromResources for BoardMaterials {
             select: materials.add(Color::rgb(0.7, 0.7, 0.7).into()),
             highlight: materials.add(Color::rgb(0.3, 0.3, 0.3).into()),
             wall_mat_handle: materials.add(Color::rgb(0.32, 0.16, 0.04).into()),
+            seam_mat_handle: materials.add(Color::rgb(0.45, 0.3, 0.1).into()),
+            hint: materials.add(Color::rgb(0.1, 0.35, 0.2).into()),
             pawn_materials: (0..pawn_count).fold(vec![], |mut vec, i| {
                 let color = to_rgb(((256 / pawn_count as u16) as u8) * i, 255, 128);
                 vec.push(materials.add(Color::rgb_u8(color.0, color.1, color.2).into()));
@@ -17,16 +17,50 @@ use systems::*;
 generate_rulebook! {
     StandardQuoridor,
     FreeQuoridor,
+    MovingWallsQuoridor,
+    RandomSetupQuoridor,
+    ToroidalQuoridor,
+    FogQuoridor,
 }
 
 pub struct MoveEvent(Move);
 #[derive(Default)]
 pub struct MoveEventListenerState(EventReader<MoveEvent>);
 
+// Accepts the short names used on the command line as well as rulebook type names
+fn parse_game_type(name: &str) -> Option<QGameType> {
+    Some(match name {
+        "standard" => QGameType::StandardQuoridor,
+        "free" => QGameType::FreeQuoridor,
+        "moving" => QGameType::MovingWallsQuoridor,
+        "random" => QGameType::RandomSetupQuoridor,
+        "torus" => QGameType::ToroidalQuoridor,
+        "fog" => QGameType::FogQuoridor,
+        _ => return QGameType::from_name(name),
+    })
+}
+
 fn main() {
-    let game_type = QGameType::StandardQuoridor;
+    let mut args: Vec<String> = std::env::args().collect();
 
-    let args: Vec<String> = std::env::args().collect();
+    // --rulebook may come anywhere; the other options read their values by position once it is taken out
+    let game_type = match args.iter().position(|arg| arg == "--rulebook") {
+        Some(i) => {
+            let name = args.get(i + 1).cloned().unwrap_or_default();
+            args.drain(i..args.len().min(i + 2));
+            match parse_game_type(&name) {
+                Some(game_type) => game_type,
+                None => {
+                    println!(
+                        "Unknown rulebook {}; use standard, free, moving, random, torus or fog",
+                        name
+                    );
+                    return;
+                }
+            }
+        }
+        None => QGameType::StandardQuoridor,
+    };
     let core;
 
     let mut threads = vec![];
@@ -39,7 +73,24 @@ fn main() {
         })
             as Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>);
         core = cores.remove(0);
-        let mut tb = cores.host(args[2].parse().unwrap(), game_type); //tbmp::remote_agent::host(vec![cores.remove(0)], args[2].parse().unwrap());
+        let port = match args.get(2).map(|port| port.parse::<u16>()) {
+            Some(Ok(port)) => port,
+            _ => {
+                println!(r"Usage: --host <PORT>");
+                return;
+            }
+        };
+        let hosted = match std::net::TcpListener::bind(("0.0.0.0", port)) {
+            Ok(listener) => cores.host(&listener),
+            Err(e) => Err(e.into()),
+        };
+        let mut tb = match hosted {
+            Ok(tb) => tb,
+            Err(e) => {
+                println!("Could not host game: {}", e);
+                return;
+            }
+        };
         let t = move || -> Result<(), Box<dyn Error>> {
             for t in tb.iter_mut() {
                 t()?;
@@ -47,12 +98,106 @@ fn main() {
             Ok(())
         };
         threads.push(Box::new(t) as Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>);
+    } else if args.contains(&String::from("--bot")) {
+        let config = match ai::BotConfig::parse(
+            args.get(2).map_or("", |s| &s[..]),
+            args.get(3).map(|s| &s[..]),
+            rng::time_seed(),
+        ) {
+            Ok(config) => config,
+            Err(_) => {
+                println!(r"Usage: --bot <beginner|easy|medium|hard|expert> [balanced|aggressive|racing]");
+                return;
+            }
+        };
+        let (mut cores, mut t) = game_type.new_game();
+        threads.push(Box::new(move || match t() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        })
+            as Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>);
+        // Searching can take seconds, so the bot gets its own thread instead of stalling frames
+        let mut bot = cores.pop().unwrap().bot(config);
+        std::thread::spawn(move || {
+            while bot().is_ok() {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        });
+        core = cores.remove(0);
+    } else if args.contains(&String::from("--engine")) {
+        let limits = match args.get(3).map(|ms| ms.parse()) {
+            None => engine::GoLimits {
+                movetime: Some(2000),
+                ..Default::default()
+            },
+            Some(Ok(ms)) => engine::GoLimits {
+                movetime: Some(ms),
+                ..Default::default()
+            },
+            Some(Err(_)) => {
+                println!(r#"Usage: --engine "<COMMAND>" [MOVETIME_MS]"#);
+                return;
+            }
+        };
+        let (mut cores, mut t) = game_type.new_game();
+        threads.push(Box::new(move || match t() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        })
+            as Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>);
+        let mut engine = match cores
+            .pop()
+            .unwrap()
+            .engine(args.get(2).map_or("", |s| &s[..]), limits)
+        {
+            Ok(engine) => engine,
+            Err(e) => {
+                println!("Could not start engine: {}", e);
+                return;
+            }
+        };
+        std::thread::spawn(move || {
+            while engine().is_ok() {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        });
+        core = cores.remove(0);
     } else if args.contains(&String::from("--connect")) {
-        let (c, t) = QAgent::connect(args[2].parse().unwrap(), game_type);
+        let addr = match args.get(2).map(|addr| addr.parse()) {
+            Some(Ok(addr)) => addr,
+            _ => {
+                println!(r"Usage: --connect <IP:PORT> [CODE]");
+                return;
+            }
+        };
+        let (c, t) = match QAgent::connect(addr, game_type, args.get(3).cloned()) {
+            Ok(agent) => agent,
+            Err(e) => {
+                println!("Could not connect: {}", e);
+                return;
+            }
+        };
+        core = c;
+        threads.push(Box::new(t) as Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>);
+    } else if args.contains(&String::from("--watch")) {
+        let (addr, game) = match (args.get(2).map(|addr| addr.parse()), args.get(3)) {
+            (Some(Ok(addr)), Some(game)) => (addr, game.clone()),
+            _ => {
+                println!(r"Usage: --watch <IP:PORT> <GAME>");
+                return;
+            }
+        };
+        let (c, t) = match QAgent::watch(addr, game_type, game) {
+            Ok(agent) => agent,
+            Err(e) => {
+                println!("Could not watch: {}", e);
+                return;
+            }
+        };
         core = c;
         threads.push(Box::new(t) as Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>);
     } else {
-        println!(r"Usage: --host <PORT> / --connect <IP:PORT>");
+        println!(r#"Usage: --host <PORT> / --connect <IP:PORT> [CODE] / --watch <IP:PORT> <GAME> / --bot <LEVEL> [STYLE] / --engine "<COMMAND>" [MOVETIME_MS], each with an optional --rulebook <NAME>"#);
         return;
     }
 
@@ -5,6 +5,7 @@ pub(crate) fn input_system(
     board_materials: Res<BoardMaterials>,
     game: Res<Quoridor>,
     side: Res<u8>,
+    keyboard_input: Res<Input<KeyCode>>,
     mut moves: ResMut<Events<MoveEvent>>,
     mut interaction_query: Query<(
         &Button,
@@ -15,6 +16,18 @@ pub(crate) fn input_system(
         Option<&Wall>,
     )>,
 ) {
+    if keyboard_input.just_pressed(KeyCode::H) && *side == game.turn_of() {
+        let limits = ai::SearchLimits {
+            max_depth: 2,
+            time_limit: Some(std::time::Duration::from_secs(1)),
+            node_limit: None,
+        };
+        if let Some(best) = game.analyse(1, limits).into_iter().next() {
+            println!("Hint: {:?} (score {}), expected line: {:?}", best.qmove, best.score, best.line);
+            state.hint = Some(best.qmove);
+        }
+    }
+
     //println!("{:?}", *state);
     for (_button, interaction, mut material, element_type, pos, wall) in
         &mut interaction_query.iter()
@@ -23,11 +36,12 @@ pub(crate) fn input_system(
             Interaction::Clicked(flags) => {
                 if let Some(pos) = pos {
                     if *element_type == BoardElement::WallSlot {
+                        let pos = seam_normalized(&game, *pos);
                         let event = MoveEvent(Move::PlaceWall({
                             if flags.check(MouseButton::Left) {
-                                Wall::vertical(*pos)
+                                Wall::vertical(pos)
                             } else {
-                                Wall::horizontal(*pos)
+                                Wall::horizontal(pos)
                             }
                         }));
                         moves.send(event);
@@ -103,28 +117,53 @@ pub(crate) fn board_update_system(
         Option<&Wall>,
     )>,
 ) {
+    let hint_wall = match state.hint {
+        Some(Move::PlaceWall(wall)) | Some(Move::MoveWall(_, wall)) => Some(wall),
+        _ => None,
+    };
+    let hint_target = match state.hint {
+        Some(Move::MovePawn(_, target)) => Some(target),
+        _ => None,
+    };
+
     //println!("{:?}", *state);
     for (_button, mut material, mut element_type, pos, wall) in &mut query.iter() {
+        let mut hinted = false;
         if let Some(wall) = wall {
+            let mut wall = wall.clone();
+            wall.position = seam_normalized(&game, wall.position);
             let mut second_wall_edge = wall.clone();
-            second_wall_edge.position = Position::from((
-                wall.position.x + (wall.orientation == Orientation::Horizontal) as u8,
-                wall.position.y + (wall.orientation == Orientation::Vertical) as u8,
-            ));
+            second_wall_edge.position = seam_normalized(
+                &game,
+                Position::from((
+                    wall.position.x + (wall.orientation == Orientation::Horizontal) as u8,
+                    wall.position.y + (wall.orientation == Orientation::Vertical) as u8,
+                )),
+            );
 
-            if game.walls().contains(wall) || game.walls().contains(&second_wall_edge) {
-                *element_type = BoardElement::Wall;
-            }
+            hinted = hint_wall == Some(wall) || hint_wall == Some(second_wall_edge);
+
+            // Walls can disappear from partial views and moving-wall games
+            *element_type =
+                if game.walls().contains(&wall) || game.walls().contains(&second_wall_edge) {
+                    BoardElement::Wall
+                } else {
+                    BoardElement::WallSlot
+                };
         }
 
         if let Some(pos) = pos {
             if let BoardElement::EmptyNode = *element_type {
             } else {
-                if game.walls().contains(&Wall::horizontal(*pos))
-                    || game.walls().contains(&Wall::vertical(*pos))
+                let pos = seam_normalized(&game, *pos);
+                hinted = hint_wall.map_or(false, |w| w.position == pos);
+                *element_type = if game.walls().contains(&Wall::horizontal(pos))
+                    || game.walls().contains(&Wall::vertical(pos))
                 {
-                    *element_type = BoardElement::Wall;
-                }
+                    BoardElement::Wall
+                } else {
+                    BoardElement::WallSlot
+                };
             }
         }
 
@@ -141,6 +180,8 @@ pub(crate) fn board_update_system(
                         } else {
                             board_materials.pawn_materials[*id as usize]
                         }
+                    } else if hint_target == Some(*pos) {
+                        board_materials.hint
                     } else {
                         board_materials.base_mat_handle
                     }
@@ -148,6 +189,10 @@ pub(crate) fn board_update_system(
                     unreachable!()
                 }
             }
+            BoardElement::WallSlot if hinted => board_materials.hint,
+            BoardElement::WallSlot if game.is_toroidal() && on_seam(pos, wall) => {
+                board_materials.seam_mat_handle
+            }
             BoardElement::WallSlot => board_materials.wall_slot_mat_handle,
             BoardElement::Wall => board_materials.wall_mat_handle,
         };
@@ -155,6 +200,10 @@ pub(crate) fn board_update_system(
 }
 
 fn owned_pawn_check(side: PlayerID, game: &Res<Quoridor>, pos: Position) -> bool {
+    // Spectators own nothing
+    if side >= game.get_player_count() {
+        return false;
+    }
     let pawns_per_player = game.get_pawn_count() / game.get_player_count();
     if let Some(&id) = game.pawns().get_by_right(&pos) {
         let min_id = side * pawns_per_player;
@@ -164,3 +213,21 @@ fn owned_pawn_check(side: PlayerID, game: &Res<Quoridor>, pos: Position) -> bool
         false
     }
 }
+
+fn seam_normalized(game: &Quoridor, pos: Position) -> Position {
+    if game.is_toroidal() {
+        Position::from((pos.x % 9, pos.y))
+    } else {
+        pos
+    }
+}
+
+fn on_seam(pos: Option<&Position>, wall: Option<&Wall>) -> bool {
+    match (pos, wall) {
+        (Some(pos), _) => pos.x == 0 || pos.x == 9,
+        (_, Some(wall)) => {
+            wall.orientation == Orientation::Vertical && (wall.position.x == 0 || wall.position.x == 9)
+        }
+        _ => false,
+    }
+}
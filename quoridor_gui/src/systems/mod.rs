@@ -14,6 +14,7 @@ pub struct GameSystemsPlugin;
 pub struct BoardState {
     highlight_pawn: Option<quoridor_core::Position>,
     can_highlight: bool,
+    hint: Option<quoridor_core::Move>,
 }
 
 impl Default for BoardState {
@@ -21,6 +22,7 @@ impl Default for BoardState {
         Self {
             highlight_pawn: None,
             can_highlight: true,
+            hint: None,
         }
     }
 }
@@ -7,6 +7,7 @@ pub(crate) fn quoridor_system(
     mut exit_timer: ResMut<ExitTimer>,
     core: Res<QAgent>,
     mut game: ResMut<Quoridor>,
+    mut board_state: ResMut<BoardState>,
     side: Res<u8>,
     mut state: Local<MoveEventListenerState>,
     moves: Res<Events<MoveEvent>>,
@@ -15,6 +16,12 @@ pub(crate) fn quoridor_system(
         match event {
             QGameEvent::MoveHappened(qmove) => {
                 game.apply_move(&qmove);
+                board_state.hint = None;
+            }
+            QGameEvent::GameStart(new_game, _) => {
+                // Hidden-information games resend the visible state after every move
+                *game = new_game;
+                board_state.hint = None;
             }
             QGameEvent::InvalidMove => println!("Invalid move!"),
             QGameEvent::GameEnd(side) => {
@@ -2,33 +2,633 @@ use bimap::BiMap;
 use crossbeam_channel::{Receiver, Sender};
 #[allow(unused_imports)]
 use quoridor_core::{rulebooks::*, *};
+use quoridor_core::archive::{self, Archive};
+use quoridor_core::net::{self, Audience, FrameReader, MatchReply, MatchRequest, Protocol, Session};
+use quoridor_core::record::GameRecord;
+use std::collections::hash_map::{Entry, HashMap};
 use std::env;
 use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
 use tbmp::*;
 
 generate_rulebook! {
     FreeQuoridor,
     StandardQuoridor,
+    MovingWallsQuoridor,
+    RandomSetupQuoridor,
+    ToroidalQuoridor,
+    FogQuoridor,
 }
 
-fn main() {
-    let args = env::args().collect::<Vec<_>>();
+const USAGE: &str = "Usage: quoridor_server_tcp --port PORT [--bind ADDR] [--rulebook NAME] [--option KEY=VALUE]... [--games N] [--grace SECONDS] [--heartbeat SECONDS] [--move-time SECONDS] [--log FILE|-] [--archive DIR]
+Rulebooks: standard, free, moving, random, torus, fog
+Options: bot=<beginner|easy|medium|hard|expert>, style=<balanced|aggressive|racing>,
+seed=N for random setups, sight=N for the fog's sight radius
+The rulebook and options are used for clients that leave the choice to the server
+Finished games are kept in the archive directory, ./archive unless given
+Players forfeit when gone longer than the grace period or over the move time, which is unlimited
+unless given; a heartbeat of 0 turns heartbeats off
+Clients can also play over a text protocol, e.g. `nc HOST PORT` and then `play standard`";
+
+struct Options {
+    bind: IpAddr,
+    port: u16,
+    game_type: QGameType,
+    rules: Vec<(String, String)>,
+    games: Option<usize>,
+    timeouts: net::Timeouts,
+    log: Option<String>,
+    archive: String,
+}
+
+// Accepts the short names used on the command line as well as rulebook type names
+fn parse_game_type(name: &str) -> Result<QGameType, String> {
+    Ok(match name {
+        "standard" => QGameType::StandardQuoridor,
+        "free" => QGameType::FreeQuoridor,
+        "moving" => QGameType::MovingWallsQuoridor,
+        "random" => QGameType::RandomSetupQuoridor,
+        "torus" => QGameType::ToroidalQuoridor,
+        "fog" => QGameType::FogQuoridor,
+        _ => return QGameType::from_name(name).ok_or_else(|| format!("Unknown rulebook {}", name)),
+    })
+}
+
+// Game records name rulebooks the way the command line does
+fn short_name(game_type: QGameType) -> &'static str {
+    match game_type {
+        QGameType::StandardQuoridor => "standard",
+        QGameType::FreeQuoridor => "free",
+        QGameType::MovingWallsQuoridor => "moving",
+        QGameType::RandomSetupQuoridor => "random",
+        QGameType::ToroidalQuoridor => "torus",
+        QGameType::FogQuoridor => "fog",
+    }
+}
+
+// The bot a table seats and the options its rulebook sets the game up with
+type Rules = (Option<ai::BotConfig>, Vec<(String, String)>);
+
+// Splits off the bot, which the server seats, from the options that go to the rulebook
+fn parse_rules(game_type: QGameType, rules: &[(String, String)]) -> Result<Rules, String> {
+    let (mut bot, mut style) = (None, None);
+    let mut rulebook = vec![];
+    for (key, value) in rules {
+        match &key[..] {
+            "bot" => bot = Some(value),
+            "style" => style = Some(value),
+            _ => rulebook.push((key.clone(), value.clone())),
+        }
+    }
+    game_type.check_options(&rulebook)?;
+    let bot = match (bot, style) {
+        (Some(level), style) => ai::BotConfig::parse(level, style.map(|s| &s[..]), 0)
+            .map(Some)
+            .map_err(|_| format!("Unknown bot level or style: {}", level))?,
+        (None, Some(_)) => return Err("style needs a bot option".into()),
+        (None, None) => None,
+    };
+    Ok((bot, rulebook))
+}
+
+fn parse_options(args: &[String]) -> Result<Options, Box<dyn Error>> {
+    let mut options = Options {
+        bind: IpAddr::from([0, 0, 0, 0]),
+        port: 0,
+        game_type: QGameType::StandardQuoridor,
+        rules: vec![],
+        games: None,
+        timeouts: net::Timeouts::default(),
+        log: None,
+        archive: "archive".into(),
+    };
+    let mut port = None;
+    let mut positional = vec![];
 
-    let game_type = QGameType::StandardQuoridor;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match &arg[..] {
+            "--bind" => {
+                let addr = value()?;
+                options.bind = addr.parse().map_err(|_| format!("Invalid bind address {}", addr))?;
+            }
+            "--port" => {
+                let p = value()?;
+                port = Some(p.parse::<u16>().map_err(|_| format!("Invalid port {}", p))?);
+            }
+            "--rulebook" => options.game_type = parse_game_type(value()?)?,
+            "--option" => {
+                let pair = value()?;
+                let mut parts = pair.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(value)) => options.rules.push((key.into(), value.into())),
+                    _ => return Err(format!("Options look like KEY=VALUE, got {}", pair).into()),
+                }
+            }
+            "--games" => {
+                let n = value()?;
+                options.games = Some(n.parse().map_err(|_| format!("Invalid game count {}", n))?);
+            }
+            "--grace" => {
+                let seconds = value()?;
+                options.timeouts.grace = Duration::from_secs(
+                    seconds.parse().map_err(|_| format!("Invalid grace period {}", seconds))?,
+                );
+            }
+            "--heartbeat" => {
+                let seconds = value()?;
+                let seconds = seconds
+                    .parse()
+                    .map_err(|_| format!("Invalid heartbeat interval {}", seconds))?;
+                options.timeouts.heartbeat = Some(Duration::from_secs(seconds)).filter(|_| seconds > 0);
+            }
+            "--move-time" => {
+                let seconds = value()?;
+                options.timeouts.move_time = Some(Duration::from_secs(
+                    seconds.parse().map_err(|_| format!("Invalid move time {}", seconds))?,
+                ));
+            }
+            "--log" => options.log = Some(value()?.clone()),
+            "--archive" => options.archive = value()?.clone(),
+            _ if arg.starts_with("--") => return Err(format!("Unknown argument {}", arg).into()),
+            _ => positional.push(arg),
+        }
+    }
 
+    // Earlier versions took <PORT> [LEVEL] [STYLE] positionally
+    match &positional[..] {
+        [] => {}
+        [p, rest @ ..] if port.is_none() && rest.len() <= 2 => {
+            port = Some(p.parse::<u16>().map_err(|_| format!("Invalid port {}", p))?);
+            for (key, value) in ["bot", "style"].iter().zip(rest) {
+                options.rules.push((key.to_string(), value.to_string()));
+            }
+        }
+        [arg, ..] => return Err(format!("Unexpected argument {}", arg).into()),
+    }
+
+    options.port = port.ok_or("Missing --port")?;
+    parse_rules(options.game_type, &options.rules)?;
+    Ok(options)
+}
+
+// Shared by every game thread; each line is written and flushed under the lock so lines never interleave
+#[derive(Clone)]
+struct Log(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl Log {
+    fn open(path: &Option<String>) -> Result<Self, Box<dyn Error>> {
+        let out: Box<dyn Write + Send> = match path {
+            None => Box::new(std::io::stdout()),
+            Some(path) if path == "-" => Box::new(std::io::stdout()),
+            Some(path) => Box::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("Could not open log {}: {}", path, e))?,
+            ),
+        };
+        Ok(Log(Arc::new(Mutex::new(out))))
+    }
+
+    fn line(&self, args: std::fmt::Arguments) {
+        if let Ok(mut out) = self.0.lock() {
+            writeln!(out, "{}", args).ok();
+            out.flush().ok();
+        }
+    }
+}
+
+// Queued streams are non-blocking, so peeking tells whether the client hung up while waiting
+fn still_open(stream: &TcpStream) -> bool {
+    match stream.peek(&mut [0u8; 1]) {
+        Ok(0) => false,
+        Ok(_) => true,
+        Err(e) => e.kind() == ErrorKind::WouldBlock,
+    }
+}
+
+type PlayerFn = Box<dyn Send + Sync + FnMut() -> Result<(), Box<dyn Error>>>;
+type GameFn = Box<dyn Send + Sync + FnMut() -> Result<MoveResult, Box<dyn Error>>>;
+
+// Where a finished game is written, and what is known about it besides the moves
+struct Archiving {
+    archive: Archive,
+    record: Arc<Mutex<GameRecord>>,
+    options: Vec<(String, String)>,
+    started: u64,
+}
+
+// Everything that runs alongside the game: relays first, then the seats by player id, then the bot
+struct Players {
+    relays: Vec<PlayerFn>,
+    seats: Vec<(PlayerFn, Arc<Mutex<dyn Session>>)>,
+    bot: Option<PlayerFn>,
+    audience: Arc<Mutex<dyn Audience>>,
+}
+
+// Runs one game to completion. A player whose seat fails, by staying away or running out of time,
+// forfeits and everyone else is told; any other failure aborts the game.
+// Games that end with a result are archived; aborted ones are only logged.
+fn play(number: usize, mut game_thread: GameFn, mut players: Players, archiving: Archiving, log: Log) {
+    let result = 'game: loop {
+        let x = game_thread();
+        for t in players.relays.iter_mut() {
+            if let Err(e) = t() {
+                break 'game Err(e);
+            }
+        }
+        for (id, (t, _)) in players.seats.iter_mut().enumerate() {
+            if let Err(e) = t() {
+                break 'game Ok(Err((id as PlayerID, e)));
+            }
+        }
+        if let Some(bot) = &mut players.bot {
+            if let Err(e) = bot() {
+                break 'game Err(e);
+            }
+        }
+        match x {
+            Ok(MoveResult::Continue) => thread::sleep(Duration::from_millis(1)),
+            Ok(result) => break Ok(Ok(result)),
+            Err(e) => break Err(e),
+        }
+    };
+    let winner = match result {
+        Ok(Ok(MoveResult::Win(id))) => {
+            log.line(format_args!("Game {}: player {} won", number, id));
+            Some(id)
+        }
+        Ok(Ok(_)) => {
+            log.line(format_args!("Game {}: draw", number));
+            None
+        }
+        Ok(Err((loser, e))) => {
+            // With two players the other one wins; with more, nobody does
+            let count = players.seats.len() + players.bot.iter().count();
+            let winner = if count == 2 { Some(1 - loser) } else { None };
+            for (_, session) in players.seats.iter() {
+                if let Ok(mut session) = session.lock() {
+                    session.forfeit(loser, winner);
+                }
+            }
+            if let Ok(mut audience) = players.audience.lock() {
+                audience.conclude(winner);
+            }
+            log.line(format_args!("Game {}: player {} forfeited ({})", number, loser, e));
+            winner
+        }
+        Err(e) => {
+            log.line(format_args!("Game {}: aborted: {}", number, e));
+            return;
+        }
+    };
+
+    let Archiving {
+        archive,
+        record,
+        options,
+        started,
+    } = archiving;
+    let mut record = match record.lock() {
+        Ok(record) => record.clone(),
+        Err(_) => return,
+    };
+    record.winner = winner;
+    match archive.store(record, options, started) {
+        Ok(id) => log.line(format_args!("Game {}: archived as {}", number, id)),
+        Err(e) => log.line(format_args!("Game {}: could not archive: {}", number, e)),
+    }
+}
+
+// A connection that has not sent its MatchRequest yet
+struct Pending {
+    stream: TcpStream,
+    addr: SocketAddr,
+    reader: FrameReader,
+    since: Instant,
+}
+
+// Players waiting for a game, seated as they arrive; the game starts once every seat is taken
+struct Table {
+    cores: Vec<QAgent>,
+    game_thread: GameFn,
+    // Filled in from the whole board, since a seat may not see every move
+    record: Arc<Mutex<GameRecord>>,
+    bot: Option<PlayerFn>,
+    // Each player's connection, address and session token
+    seated: Vec<(TcpStream, Protocol, SocketAddr, String)>,
+}
+
+impl Table {
+    fn new(game_type: QGameType, bot: Option<ai::BotConfig>, rules: &[(String, String)]) -> Result<Self, String> {
+        let record = Arc::new(Mutex::new(GameRecord::new(short_name(game_type), vec![])));
+        let (mut cores, game_thread) = game_type.new_recorded_game_with(record.clone(), rules)?;
+        let bot = bot.map(|config| {
+            cores.pop().unwrap().bot(ai::BotConfig {
+                seed: rng::time_seed(),
+                ..config
+            })
+        });
+        Ok(Table {
+            cores,
+            game_thread,
+            record,
+            bot,
+            seated: vec![],
+        })
+    }
+
+    fn full(&self) -> bool {
+        self.seated.len() >= self.cores.len()
+    }
+}
+
+// Works out which table a request belongs at, and the bot and rule options a new table gets;
+// requests that leave the rulebook empty get the server's defaults
+fn match_key(request: MatchRequest, options: &Options) -> Result<(MatchRequest, QGameType, Rules), String> {
+    let (game_type, mut rules) = if request.rulebook.is_empty() {
+        (options.game_type, options.rules.clone())
+    } else {
+        (parse_game_type(&request.rulebook)?, request.options)
+    };
+    let rules_for_table = parse_rules(game_type, &rules)?;
+    rules.sort();
+    let key = MatchRequest {
+        rulebook: game_type.name().into(),
+        options: rules,
+        code: request.code.filter(|code| !code.is_empty()),
+        watch: None,
+        resume: None,
+    };
+    Ok((key, game_type, rules_for_table))
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let log = Log::open(&options.log)?;
+    let archive = Archive::open(&options.archive)?;
+
+    let listener = TcpListener::bind((options.bind, options.port))
+        .map_err(|e| format!("Could not listen on {}:{}: {}", options.bind, options.port, e))?;
+    listener.set_nonblocking(true)?;
+    log.line(format_args!(
+        "Listening on {}, {:?} by default",
+        listener.local_addr()?,
+        options.game_type
+    ));
+
+    let mut games = vec![];
+    let mut pending: Vec<Pending> = vec![];
+    let mut tables: HashMap<MatchRequest, Table> = HashMap::new();
+    // Running games by number and private code, with their rulebook; entries lapse when the game ends
+    let mut watchable: HashMap<String, (String, Weak<Mutex<dyn Audience>>)> = HashMap::new();
+    // Seats in running games by session token; a seat lapses when its game ends
+    let mut sessions: HashMap<String, Weak<Mutex<dyn Session>>> = HashMap::new();
+    // Once the last game has started, only spectators are let in until it is over
     loop {
-        println!("NEW GAME");
-        let (cores, mut game_thread) = game_type.new_game();
-        let mut player_threads = cores.host(args[1].parse().unwrap(), game_type);
+        let closed = options.games.map_or(false, |limit| games.len() >= limit);
+        if closed && watchable.is_empty() {
+            break;
+        }
+
         loop {
-            let x = game_thread();
-            for t in player_threads.iter_mut() {
-                t().ok();
+            match listener.accept() {
+                Ok((stream, addr)) => {
+                    if stream.set_nonblocking(true).is_ok() {
+                        log.line(format_args!("{} connected", addr));
+                        pending.push(Pending {
+                            stream,
+                            addr,
+                            reader: FrameReader::default(),
+                            since: Instant::now(),
+                        });
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log.line(format_args!("Could not accept a connection: {}", e));
+                    break;
+                }
+            }
+        }
+
+        let mut requests = vec![];
+        for mut p in pending.drain(..).collect::<Vec<_>>() {
+            match p.reader.poll_request(&mut p.stream) {
+                Ok(Some((request, protocol))) => requests.push((p.stream, protocol, p.addr, request)),
+                Ok(None) if p.since.elapsed() < net::HANDSHAKE_TIMEOUT => pending.push(p),
+                _ => log.line(format_args!("{} dropped before asking for a game", p.addr)),
+            }
+        }
+
+        for table in tables.values_mut() {
+            table.seated.retain(|(stream, _, _, _)| still_open(stream));
+        }
+        tables.retain(|_, table| !table.seated.is_empty());
+        watchable.retain(|_, (_, audience)| audience.upgrade().is_some());
+        sessions.retain(|_, session| session.upgrade().is_some());
+
+        for (mut stream, protocol, addr, request) in requests {
+            if let Some(token) = &request.resume {
+                match sessions.get(token).and_then(Weak::upgrade) {
+                    Some(session) => {
+                        let reply = MatchReply::Queued(Some(token.clone()));
+                        let resumed = net::write_reply(&mut stream, protocol, &reply).and_then(|_| {
+                            session.lock().map_err(|_| "seat poisoned")?.resume(stream, protocol)
+                        });
+                        match resumed {
+                            Ok(()) => log.line(format_args!("{} took back their seat", addr)),
+                            Err(e) => log.line(format_args!("{} could not take back their seat: {}", addr, e)),
+                        }
+                    }
+                    None => {
+                        log.line(format_args!("{} turned away: no game to resume", addr));
+                        let reply = MatchReply::Rejected("There is no game to resume".into());
+                        net::write_reply(&mut stream, protocol, &reply).ok();
+                    }
+                }
+                continue;
+            }
+
+            if let Some(game) = &request.watch {
+                let found = watchable.get(game).and_then(|(rulebook, audience)| {
+                    let fits = request.rulebook.is_empty() || request.rulebook == *rulebook;
+                    let hidden = QGameType::from_name(rulebook).map_or(false, |gt| gt.hides_information());
+                    audience.upgrade().filter(|_| fits).map(|audience| (audience, hidden))
+                });
+                match found {
+                    // The spectators' view comes from a seat, which only sees its own side of the board
+                    Some((_, true)) => {
+                        let reason = format!("Game {} hides information, so it can't be watched", game);
+                        log.line(format_args!("{} turned away: {}", addr, reason));
+                        net::write_reply(&mut stream, protocol, &MatchReply::Rejected(reason)).ok();
+                    }
+                    Some((audience, false)) => {
+                        if net::write_reply(&mut stream, protocol, &MatchReply::Watching).is_ok() {
+                            if let Ok(mut audience) = audience.lock() {
+                                audience.join(stream, protocol).ok();
+                            }
+                            log.line(format_args!("{} is watching game {}", addr, game));
+                        }
+                    }
+                    None => {
+                        let reason = format!("No running {} game {}", request.rulebook, game);
+                        log.line(format_args!("{} turned away: {}", addr, reason));
+                        net::write_reply(&mut stream, protocol, &MatchReply::Rejected(reason)).ok();
+                    }
+                }
+                continue;
+            }
+
+            let admitted = match_key(request, options).and_then(|(key, game_type, rules)| {
+                if options.games.map_or(false, |limit| games.len() >= limit) {
+                    return Err("This server is not starting any more games".to_string());
+                }
+                let taken = tables
+                    .keys()
+                    .any(|other| other.code.is_some() && other.code == key.code && *other != key);
+                if taken {
+                    Err(format!("Code {} is already used for a different game", key.code.as_ref().unwrap()))
+                } else {
+                    Ok((key, game_type, rules))
+                }
+            });
+            let admitted = admitted.and_then(|(key, game_type, (bot, rules))| match tables.entry(key.clone()) {
+                Entry::Occupied(table) => Ok((key, table.into_mut())),
+                Entry::Vacant(seat) => Ok((key, seat.insert(Table::new(game_type, bot, &rules)?))),
+            });
+            let (key, table) = match admitted {
+                Ok(admitted) => admitted,
+                Err(reason) => {
+                    log.line(format_args!("{} turned away: {}", addr, reason));
+                    net::write_reply(&mut stream, protocol, &MatchReply::Rejected(reason)).ok();
+                    continue;
+                }
+            };
+            let token = match net::session_token() {
+                Ok(token) => token,
+                Err(e) => {
+                    log.line(format_args!("{} turned away: no session token: {}", addr, e));
+                    let reply = MatchReply::Rejected("The server could not start a session".into());
+                    net::write_reply(&mut stream, protocol, &reply).ok();
+                    continue;
+                }
+            };
+            if net::write_reply(&mut stream, protocol, &MatchReply::Queued(Some(token.clone()))).is_err() {
+                continue;
+            }
+            log.line(format_args!("{} queued for {}", addr, describe(&key)));
+
+            table.seated.push((stream, protocol, addr, token));
+            if !table.full() {
+                continue;
+            }
+
+            let table = tables.remove(&key).unwrap();
+            let number = games.len() + 1;
+            let mut players = table
+                .seated
+                .iter()
+                .map(|(_, _, addr, _)| addr.to_string())
+                .collect::<Vec<_>>();
+            if table.bot.is_some() {
+                players.push("bot".into());
+            }
+            // Spectators follow the game through the first seat
+            let record = table.record;
+            if let Ok(mut record) = record.lock() {
+                record.players = players.clone();
             }
-            match x {
-                Ok(MoveResult::Continue) => {}
-                _ => break,
+            let mut cores = table.cores;
+            let (first, audience, relay) = cores.remove(0).tap();
+            cores.insert(0, first);
+            let seated = cores
+                .into_iter()
+                .zip(table.seated)
+                .map(|(core, (stream, protocol, _, token))| {
+                    Ok((token, core.seat(stream, protocol, options.timeouts)?))
+                })
+                .collect::<Result<Vec<_>, Box<dyn Error>>>();
+            let seated = match seated {
+                Ok(seated) => seated,
+                Err(e) => {
+                    log.line(format_args!("Game {}: could not seat players: {}", number, e));
+                    continue;
+                }
+            };
+            // The relays run first so the first seat has its events, including the last, before it runs
+            let mut lineup = Players {
+                relays: vec![relay],
+                seats: vec![],
+                bot: table.bot,
+                audience: audience.clone(),
+            };
+            for (token, (session, t)) in seated {
+                sessions.insert(token, Arc::downgrade(&session));
+                lineup.seats.push((t, session));
             }
+
+            for name in Some(number.to_string()).into_iter().chain(key.code.clone()) {
+                watchable.insert(name, (key.rulebook.clone(), Arc::downgrade(&audience)));
+            }
+
+            log.line(format_args!(
+                "Game {}: {} started for {}",
+                number,
+                describe(&key),
+                players.join(", ")
+            ));
+            let archiving = Archiving {
+                archive: archive.clone(),
+                record,
+                options: key.options.clone(),
+                started: archive::now(),
+            };
+            let game_thread = table.game_thread;
+            let log = log.clone();
+            games.push(thread::spawn(move || {
+                play(number, game_thread, lineup, archiving, log)
+            }));
         }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    for game in games {
+        game.join().ok();
+    }
+    Ok(())
+}
+
+fn describe(key: &MatchRequest) -> String {
+    let mut text = key.rulebook.clone();
+    for (option, value) in key.options.iter() {
+        text += &format!(" {}={}", option, value);
+    }
+    if let Some(code) = &key.code {
+        text += &format!(" (private, code {})", code);
+    }
+    text
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n{}", e, USAGE);
+            return;
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        println!("{}", e);
     }
 }
@@ -0,0 +1,214 @@
+use quoridor_core::{ai::*, notation::*, record::GameRecord, rulebooks::*, *};
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::time::Duration;
+use tbmp::PlayerID;
+
+const USAGE: &str = "Usage: analyse <RECORDS> [--game N] [--depth N] [--time MS] [--json] [--out FILE]
+Records are JSON game records, one per line, as written by the tournament runner";
+
+struct Options {
+    records: String,
+    game: Option<usize>,
+    limits: SearchLimits,
+    json: bool,
+    out: Option<String>,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, Box<dyn Error>> {
+    let mut records = None;
+    let mut options = Options {
+        records: String::new(),
+        game: None,
+        limits: SearchLimits {
+            max_depth: 3,
+            time_limit: Some(Duration::from_secs(2)),
+            node_limit: None,
+        },
+        json: false,
+        out: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match &arg[..] {
+            "--game" => options.game = Some(value()?.parse()?),
+            "--depth" => options.limits.max_depth = value()?.parse()?,
+            "--time" => {
+                options.limits.time_limit = Some(Duration::from_millis(value()?.parse()?))
+            }
+            "--json" => options.json = true,
+            "--out" => options.out = Some(value()?.clone()),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg).into()),
+            _ if records.is_none() => records = Some(arg.clone()),
+            _ => return Err("Expected a single record file".into()),
+        }
+    }
+
+    options.records = records.ok_or("Missing record file")?;
+    Ok(options)
+}
+
+#[derive(Serialize)]
+struct GameReport {
+    game: usize,
+    record: GameRecord,
+    reviews: Vec<MoveReview>,
+}
+
+fn review<Rb: Rulebook<Move = Move>>(
+    record: &GameRecord,
+    limits: SearchLimits,
+) -> Result<Vec<MoveReview>, Box<dyn Error>> {
+    let positions = record
+        .positions::<Rb>()
+        .map_err(|ply| format!("illegal move at ply {}", ply + 1))?;
+    Ok(review_game(&positions, &record.moves, limits))
+}
+
+fn review_record(
+    record: &GameRecord,
+    limits: SearchLimits,
+) -> Result<Vec<MoveReview>, Box<dyn Error>> {
+    // Randomised setups can only be replayed from records that note their start
+    match &record.rulebook[..] {
+        "standard" => review::<StandardQuoridor>(record, limits),
+        "free" => review::<FreeQuoridor>(record, limits),
+        "moving" => review::<MovingWallsQuoridor>(record, limits),
+        "torus" => review::<ToroidalQuoridor>(record, limits),
+        "fog" => review::<FogQuoridor>(record, limits),
+        "random" if record.start.is_some() => review::<RandomSetupQuoridor>(record, limits),
+        rulebook => Err(format!("Cannot analyse {} games", rulebook).into()),
+    }
+}
+
+fn move_text(record: &GameRecord, qmove: Move) -> String {
+    let text = match &record.rulebook[..] {
+        "torus" => move_to_string::<ToroidalQuoridor>(qmove),
+        _ => move_to_string::<StandardQuoridor>(qmove),
+    };
+    text.unwrap_or_else(|_| format!("{:?}", qmove))
+}
+
+fn write_text(out: &mut dyn Write, report: &GameReport) -> Result<(), Box<dyn Error>> {
+    let record = &report.record;
+    let player = |id: PlayerID| {
+        record
+            .players
+            .get(id as usize)
+            .map_or(format!("player {}", id), |name| format!("player {} ({})", id, name))
+    };
+
+    writeln!(out, "Game {}: {}", report.game + 1, record.players.join(" vs "))?;
+    match record.winner {
+        Some(id) => writeln!(out, "Winner: {}", player(id))?,
+        None => writeln!(out, "No winner")?,
+    }
+    writeln!(
+        out,
+        "{:>4}  {:>6}  {:<8} {:>6}  {:<8} {:>6}  {}",
+        "ply", "player", "played", "eval", "best", "eval", "judgement"
+    )?;
+    for r in &report.reviews {
+        let flagged = !matches!(r.judgement, Judgement::Best | Judgement::Good);
+        write!(
+            out,
+            "{:>4}  {:>6}  {:<8} {:>+6}  {:<8} {:>+6}",
+            r.ply + 1,
+            r.player,
+            move_text(record, r.played),
+            r.played_score,
+            move_text(record, r.best.qmove),
+            r.best.score
+        )?;
+        if flagged {
+            let line = r
+                .best
+                .line
+                .iter()
+                .map(|&m| move_text(record, m))
+                .collect::<Vec<_>>()
+                .join(" ");
+            write!(out, "  {:?} (best line: {})", r.judgement, line)?;
+        }
+        writeln!(out)?;
+    }
+
+    for id in 0..record.players.len().max(2) as PlayerID {
+        let own = report.reviews.iter().filter(|r| r.player == id).collect::<Vec<_>>();
+        let count = |j: Judgement| own.iter().filter(|r| r.judgement == j).count();
+        let total_loss = own
+            .iter()
+            .map(|r| (r.best.score - r.played_score).min(BLUNDER_LOSS * 2) as f64)
+            .sum::<f64>();
+        writeln!(
+            out,
+            "{}: {} blunders, {} mistakes, {} inaccuracies, average loss {:.1}",
+            player(id),
+            count(Judgement::Blunder),
+            count(Judgement::Mistake),
+            count(Judgement::Inaccuracy),
+            total_loss / own.len().max(1) as f64
+        )?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let mut out: Box<dyn Write> = match &options.out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(std::io::stdout()),
+    };
+
+    let mut reports = vec![];
+    for (index, line) in BufReader::new(File::open(&options.records)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || options.game.map_or(false, |game| game != index + 1) {
+            continue;
+        }
+        let record: GameRecord = serde_json::from_str(&line)?;
+        let reviews = match review_record(&record, options.limits) {
+            Ok(reviews) => reviews,
+            Err(e) => {
+                eprintln!("Skipping game {}: {}", index + 1, e);
+                continue;
+            }
+        };
+        let report = GameReport {
+            game: index,
+            record,
+            reviews,
+        };
+        if options.json {
+            reports.push(report);
+        } else {
+            write_text(&mut out, &report)?;
+        }
+    }
+
+    if options.json {
+        serde_json::to_writer_pretty(&mut out, &reports)?;
+        writeln!(out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n{}", e, USAGE);
+            return;
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        println!("Analysis failed: {}", e);
+    }
+}
@@ -0,0 +1,109 @@
+use quoridor_core::archive::{Archive, Query};
+use std::error::Error;
+use std::io::Write;
+
+const USAGE: &str = "Usage: archive <DIR> list [--rulebook NAME] [--player NAME] [--limit N]
+       archive <DIR> show <ID>
+       archive <DIR> records [--rulebook NAME] [--player NAME] [--limit N]
+The directory is a server's game archive; records prints the games as JSON game records,
+one per line, for analyse and export";
+
+enum Command {
+    List(Query),
+    Show(String),
+    Records(Query),
+}
+
+struct Options {
+    dir: String,
+    command: Command,
+}
+
+fn parse_query(args: &[String]) -> Result<Query, Box<dyn Error>> {
+    let mut query = Query::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match &arg[..] {
+            "--rulebook" => query.rulebook = Some(value()?.clone()),
+            "--player" => query.player = Some(value()?.clone()),
+            "--limit" => query.limit = Some(value()?.parse()?),
+            _ => return Err(format!("Unknown option {}", arg).into()),
+        }
+    }
+    Ok(query)
+}
+
+fn parse_options(args: &[String]) -> Result<Options, Box<dyn Error>> {
+    let (dir, command, rest) = match args {
+        [dir, command, rest @ ..] => (dir.clone(), command, rest),
+        _ => return Err("Missing archive directory or command".into()),
+    };
+    let command = match (&command[..], rest) {
+        ("list", rest) => Command::List(parse_query(rest)?),
+        ("records", rest) => Command::Records(parse_query(rest)?),
+        ("show", [id]) => Command::Show(id.clone()),
+        ("show", _) => return Err("Expected a single game id".into()),
+        (command, _) => return Err(format!("Unknown command {}", command).into()),
+    };
+    Ok(Options { dir, command })
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let archive = Archive::open(&options.dir)?;
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+
+    match &options.command {
+        Command::List(query) => {
+            for game in archive.list(query)? {
+                let result = match game.winner {
+                    Some(winner) => format!("player {} won", winner),
+                    None => "draw".into(),
+                };
+                let mut options = String::new();
+                for (key, value) in game.options.iter() {
+                    options += &format!(" {}={}", key, value);
+                }
+                writeln!(
+                    out,
+                    "{}  {}{}  {} moves in {}s, {}  {}",
+                    game.id,
+                    game.rulebook,
+                    options,
+                    game.moves,
+                    game.ended.saturating_sub(game.started),
+                    result,
+                    game.players.join(" vs ")
+                )?;
+            }
+        }
+        Command::Show(id) => {
+            serde_json::to_writer_pretty(&mut out, &archive.fetch(id)?)?;
+            writeln!(out)?;
+        }
+        Command::Records(query) => {
+            // Listings are newest first; records keep the order the games were played in
+            for game in archive.list(query)?.iter().rev() {
+                serde_json::to_writer(&mut out, &archive.fetch(&game.id)?.record)?;
+                writeln!(out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n{}", e, USAGE);
+            return;
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        println!("{}", e);
+    }
+}
@@ -0,0 +1,75 @@
+use quoridor_core::{ai::*, record::GameRecord, rulebooks::*};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter};
+
+const USAGE: &str = "Usage: build_book <RECORDS.jsonl>... [--out FILE] [--plies N] [--min-weight N]";
+
+// Every appearance of a move counts once, and twice more when its side went on to win
+const PLAYED_WEIGHT: u32 = 1;
+const WIN_BONUS: u32 = 2;
+
+fn add_records(book: &mut OpeningBook, path: &str, plies: usize) -> Result<usize, Box<dyn Error>> {
+    let mut added = 0;
+    for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: GameRecord = serde_json::from_str(&line)
+            .map_err(|e| format!("{}:{}: {}", path, number + 1, e))?;
+        if record.rulebook != "standard" {
+            continue;
+        }
+
+        let positions = match record.positions::<StandardQuoridor>() {
+            Ok(positions) => positions,
+            Err(ply) => {
+                println!("{}:{}: illegal move at ply {}, skipped", path, number + 1, ply + 1);
+                continue;
+            }
+        };
+        for (game, &qmove) in positions.iter().zip(record.moves.iter()).take(plies) {
+            let weight = if record.winner == Some(game.turn_of) {
+                PLAYED_WEIGHT + WIN_BONUS
+            } else {
+                PLAYED_WEIGHT
+            };
+            book.add(game, qmove, weight);
+        }
+        added += 1;
+    }
+    Ok(added)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut inputs = vec![];
+    let mut out = String::from("book.json");
+    let mut plies = 12;
+    let mut min_weight = 2;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("Missing value for {}\n{}", arg, USAGE));
+        match &arg[..] {
+            "--out" => out = value()?,
+            "--plies" => plies = value()?.parse()?,
+            "--min-weight" => min_weight = value()?.parse()?,
+            _ => inputs.push(arg),
+        }
+    }
+    if inputs.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    let mut book = OpeningBook::new();
+    for input in inputs.iter() {
+        let games = add_records(&mut book, input, plies)?;
+        println!("{}: {} games", input, games);
+    }
+    book.prune(min_weight);
+
+    serde_json::to_writer(BufWriter::new(File::create(&out)?), &book)?;
+    println!("{} positions written to {}", book.entries.len(), out);
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use quoridor_core::{ai::*, engine::*, notation::*, rulebooks::*, *};
+use std::io::BufRead;
+
+// Speaks the engine protocol on stdin/stdout; searches run to completion, so there is no stop command
+struct Engine {
+    rulebook: String,
+    max_depth: u8,
+    qfen: Option<String>,
+    moves: Vec<String>,
+}
+
+fn think<Rb: Rulebook<Move = Move>>(engine: &Engine, limits: &GoLimits) {
+    let game = match setup_position::<Rb>(engine.qfen.as_deref(), &engine.moves) {
+        Ok(game) => game,
+        Err(_) => {
+            println!("info string invalid position");
+            println!("{}", EngineReply::BestMove(None));
+            return;
+        }
+    };
+
+    let time_limit = match (limits.budget(game.turn_of), limits.depth) {
+        (Some(budget), _) => Some(budget),
+        (None, Some(_)) => None,
+        (None, None) => SearchLimits::default().time_limit,
+    };
+    let mut search = AlphaBeta::<Rb>::new(SearchLimits {
+        max_depth: limits.depth.unwrap_or(engine.max_depth),
+        time_limit,
+        node_limit: None,
+    });
+
+    let result = search.search_with(&game, |result| {
+        println!(
+            "{}",
+            EngineReply::Info {
+                depth: result.depth,
+                score: result.score,
+                nodes: result.nodes,
+                pv: result
+                    .line
+                    .iter()
+                    .map_while(|&m| move_to_string::<Rb>(m).ok())
+                    .collect(),
+            }
+        );
+    });
+    let best = result.and_then(|result| move_to_string::<Rb>(result.best_move).ok());
+    println!("{}", EngineReply::BestMove(best));
+}
+
+fn main() {
+    let mut engine = Engine {
+        rulebook: "StandardQuoridor".into(),
+        max_depth: 4,
+        qfen: None,
+        moves: vec![],
+    };
+
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let command = match EngineCommand::parse(&line) {
+            Ok(command) => command,
+            Err(_) => {
+                println!("info string unknown command {}", line.trim());
+                continue;
+            }
+        };
+
+        match command {
+            EngineCommand::Qei => {
+                println!("id name quoridor alpha-beta");
+                println!("id author quoridor_tools");
+                println!("option name Rulebook type string default StandardQuoridor");
+                println!("option name Depth type spin default 4");
+                println!("{}", EngineReply::QeiOk);
+            }
+            EngineCommand::IsReady => println!("{}", EngineReply::ReadyOk),
+            EngineCommand::SetOption { name, value } => match &name[..] {
+                "Rulebook" => engine.rulebook = value,
+                "Depth" => match value.parse() {
+                    Ok(depth) => engine.max_depth = depth,
+                    Err(_) => println!("info string invalid depth {}", value),
+                },
+                _ => println!("info string unknown option {}", name),
+            },
+            EngineCommand::NewGame => {
+                engine.qfen = None;
+                engine.moves.clear();
+            }
+            EngineCommand::Position { qfen, moves } => {
+                engine.qfen = qfen;
+                engine.moves = moves;
+            }
+            EngineCommand::Go(limits) => match &engine.rulebook[..] {
+                "StandardQuoridor" => think::<StandardQuoridor>(&engine, &limits),
+                "FreeQuoridor" => think::<FreeQuoridor>(&engine, &limits),
+                "MovingWallsQuoridor" => think::<MovingWallsQuoridor>(&engine, &limits),
+                "RandomSetupQuoridor" => think::<RandomSetupQuoridor>(&engine, &limits),
+                "ToroidalQuoridor" => think::<ToroidalQuoridor>(&engine, &limits),
+                "FogQuoridor" => think::<FogQuoridor>(&engine, &limits),
+                rulebook => {
+                    println!("info string unsupported rulebook {}", rulebook);
+                    println!("{}", EngineReply::BestMove(None));
+                }
+            },
+            EngineCommand::Quit => break,
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use quoridor_core::{ai::*, env::*, record::GameRecord, rulebooks::*, *};
+use quoridor_tools::{bots::parse_bot, npy::write_npy};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use tbmp::MoveResult;
+
+const USAGE: &str = "Usage: export [RECORDS.jsonl]... [--self-play N] [--bot SPEC] [--rulebook standard|torus] [--out PREFIX] [--mirror] [--eval DEPTH] [--masks] [--max-plies N] [--seed N]
+Writes PREFIX_obs.npy, PREFIX_policy.npy and PREFIX_value.npy, plus PREFIX_eval.npy and PREFIX_mask.npy when asked for";
+
+struct Options {
+    records: Vec<String>,
+    self_play: usize,
+    bot: String,
+    rulebook: String,
+    out: String,
+    mirror: bool,
+    eval_depth: Option<u8>,
+    masks: bool,
+    max_plies: usize,
+    seed: u64,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, Box<dyn Error>> {
+    let mut options = Options {
+        records: vec![],
+        self_play: 0,
+        bot: "medium".into(),
+        rulebook: "standard".into(),
+        out: "samples".into(),
+        mirror: false,
+        eval_depth: None,
+        masks: false,
+        max_plies: 400,
+        seed: rng::time_seed(),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match &arg[..] {
+            "--self-play" => options.self_play = value()?.parse()?,
+            "--bot" => options.bot = value()?.clone(),
+            "--rulebook" => options.rulebook = value()?.clone(),
+            "--out" => options.out = value()?.clone(),
+            "--mirror" => options.mirror = true,
+            "--eval" => options.eval_depth = Some(value()?.parse()?),
+            "--masks" => options.masks = true,
+            "--max-plies" => options.max_plies = value()?.parse()?,
+            "--seed" => options.seed = value()?.parse()?,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg).into()),
+            _ => options.records.push(arg.clone()),
+        }
+    }
+
+    if options.records.is_empty() && options.self_play == 0 {
+        return Err("Nothing to export".into());
+    }
+    Ok(options)
+}
+
+#[derive(Default)]
+struct Samples {
+    count: usize,
+    observations: Vec<f32>,
+    policy: Vec<i64>,
+    value: Vec<f32>,
+    eval: Vec<f32>,
+    masks: Vec<bool>,
+}
+
+impl Samples {
+    fn add<Rb: Rulebook<Move = Move>>(
+        &mut self,
+        options: &Options,
+        game: &QGame<Rb>,
+        qmove: Move,
+        starting_walls: &[u8],
+        outcome: f32,
+        eval: Option<f32>,
+    ) {
+        let action = match move_to_action(game, qmove) {
+            Some(action) => action,
+            None => return,
+        };
+        self.count += 1;
+        self.observations.extend(observe(game, starting_walls));
+        self.policy.push(action as i64);
+        self.value.push(outcome);
+        if let Some(eval) = eval {
+            self.eval.push(eval);
+        }
+        if options.masks {
+            self.masks.extend(action_mask(game));
+        }
+    }
+
+    fn add_record<Rb: Rulebook<Move = Move>>(&mut self, options: &Options, record: &GameRecord) -> Result<(), usize> {
+        let positions = record.positions::<Rb>()?;
+        let starting_walls = positions[0].wall_counts.clone();
+        let mut engine = options.eval_depth.map(|max_depth| {
+            AlphaBeta::<Rb>::new(SearchLimits {
+                max_depth,
+                time_limit: None,
+                node_limit: None,
+            })
+        });
+
+        for (game, &qmove) in positions.iter().zip(record.moves.iter()) {
+            // Labels are from the side to move, like the observations
+            let outcome = match record.winner {
+                Some(id) if id == game.turn_of => 1.0,
+                Some(_) => -1.0,
+                None => 0.0,
+            };
+            let eval = engine
+                .as_mut()
+                .map(|engine| engine.search(game).map_or(0.0, |result| result.score as f32));
+
+            self.add(options, game, qmove, &starting_walls, outcome, eval);
+            // The rules are symmetric left to right, so the mirror image is an equally valid sample
+            if options.mirror {
+                let mirrored = mirror_game(game);
+                self.add(options, &mirrored, mirror_move::<Rb>(qmove), &starting_walls, outcome, eval);
+            }
+        }
+        Ok(())
+    }
+
+    fn write<Rb: Rulebook>(&self, options: &Options) -> Result<(), Box<dyn Error>> {
+        let [planes, rows, columns] = observation_shape::<Rb>();
+        let prefix = &options.out;
+        write_npy(
+            &format!("{}_obs.npy", prefix),
+            &[self.count, planes, rows, columns],
+            &self.observations,
+        )?;
+        write_npy(&format!("{}_policy.npy", prefix), &[self.count], &self.policy)?;
+        write_npy(&format!("{}_value.npy", prefix), &[self.count], &self.value)?;
+        if options.eval_depth.is_some() {
+            write_npy(&format!("{}_eval.npy", prefix), &[self.count], &self.eval)?;
+        }
+        if options.masks {
+            write_npy(
+                &format!("{}_mask.npy", prefix),
+                &[self.count, action_count::<Rb>()],
+                &self.masks,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn self_play<Rb: Rulebook<Move = Move>>(
+    options: &Options,
+    seeds: &mut rng::SplitMix64,
+) -> Result<GameRecord, Box<dyn Error>> {
+    let mut bots = vec![
+        parse_bot::<Rb>(&options.bot, seeds.next_u64())?,
+        parse_bot::<Rb>(&options.bot, seeds.next_u64())?,
+    ];
+    let mut record = GameRecord::new(&options.rulebook, vec![options.bot.clone(), options.bot.clone()]);
+    let mut game = Rb::initial_server();
+
+    while record.moves.len() < options.max_plies {
+        let qmove = match bots[game.turn_of as usize].choose_move(&game) {
+            Some(qmove) if Rb::validate_move(&game, qmove).is_ok() => qmove,
+            _ => return Err("bot failed to produce a legal move".into()),
+        };
+        record.moves.push(qmove);
+        if let MoveResult::Win(id) = Rb::apply_move(&mut game, qmove) {
+            record.winner = Some(id);
+            break;
+        }
+    }
+    Ok(record)
+}
+
+fn export<Rb: Rulebook<Move = Move>>(options: &Options) -> Result<(), Box<dyn Error>> {
+    let mut samples = Samples::default();
+
+    for path in options.records.iter() {
+        for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: GameRecord = serde_json::from_str(&line)
+                .map_err(|e| format!("{}:{}: {}", path, number + 1, e))?;
+            if record.rulebook != options.rulebook {
+                continue;
+            }
+            if let Err(ply) = samples.add_record::<Rb>(options, &record) {
+                println!("{}:{}: illegal move at ply {}, skipped", path, number + 1, ply + 1);
+            }
+        }
+    }
+
+    let mut seeds = rng::SplitMix64::new(options.seed);
+    for game in 0..options.self_play {
+        let record = self_play::<Rb>(options, &mut seeds)?;
+        samples
+            .add_record::<Rb>(options, &record)
+            .map_err(|ply| format!("self-play game {} has an illegal move at ply {}", game + 1, ply + 1))?;
+    }
+
+    samples.write::<Rb>(options)?;
+    println!("{} samples written to {}_*.npy", samples.count, options.out);
+    Ok(())
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n{}", e, USAGE);
+            return;
+        }
+    };
+
+    let result = match &options.rulebook[..] {
+        "standard" => export::<StandardQuoridor>(&options),
+        "torus" => export::<ToroidalQuoridor>(&options),
+        rulebook => Err(format!("Cannot export {} games", rulebook).into()),
+    };
+    if let Err(e) = result {
+        println!("Export failed: {}", e);
+    }
+}
@@ -0,0 +1,130 @@
+use quoridor_core::{rulebooks::*, solver::*, *};
+use quoridor_tools::bots::parse_bot;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+const USAGE: &str = "Usage: solve <3|4|5> [--out FILE] [--check BOT] [--samples N] [--seed N]";
+
+struct Options {
+    out: Option<String>,
+    check: Option<String>,
+    samples: usize,
+    seed: u64,
+}
+
+fn run<Rb: Rulebook<Move = Move>>(options: &Options) -> Result<(), Box<dyn Error>> {
+    let table = solve::<Rb>().map_err(|_| "board too large to solve")?;
+
+    let (mut wins, mut losses, mut draws) = (0, 0, 0);
+    for value in table.values.values() {
+        match value {
+            Value::Win(_) => wins += 1,
+            Value::Loss(_) => losses += 1,
+            Value::Draw => draws += 1,
+        }
+    }
+    println!(
+        "{}x{}: {} positions ({} won, {} lost, {} drawn for the side to move)",
+        Rb::BOARD_SIZE,
+        Rb::BOARD_SIZE,
+        table.values.len(),
+        wins,
+        losses,
+        draws
+    );
+    println!(
+        "Initial position: {:?}, best moves {:?}",
+        table.value(&Rb::initial_server()),
+        table.best_moves(&Rb::initial_server())
+    );
+
+    if let Some(spec) = &options.check {
+        check_bot::<Rb>(&table, spec, options)?;
+    }
+
+    if let Some(out) = &options.out {
+        serde_json::to_writer(BufWriter::new(File::create(out)?), &table)?;
+        println!("Table written to {}", out);
+    }
+    Ok(())
+}
+
+// Compares the bot's choices against perfect play on randomly sampled positions
+fn check_bot<Rb: Rulebook<Move = Move>>(
+    table: &SolutionTable,
+    spec: &str,
+    options: &Options,
+) -> Result<(), Box<dyn Error>> {
+    let mut rng = rng::SplitMix64::new(options.seed);
+    let mut bot = parse_bot::<Rb>(spec, rng.next_u64())?;
+    let mut keys = table
+        .values
+        .iter()
+        .filter(|(_, &value)| value != Value::Loss(0))
+        .map(|(&key, _)| key)
+        .collect::<Vec<_>>();
+    keys.sort();
+
+    let (mut optimal, mut preserved, mut illegal) = (0, 0, 0);
+    let samples = options.samples.min(keys.len());
+    for _ in 0..samples {
+        let game = decode::<Rb>(keys[rng.below(keys.len() as u64) as usize]);
+        let expected = table.value(&game).unwrap();
+        let qmove = match bot.choose_move(&game) {
+            Some(qmove) if Rb::validate_move(&game, qmove).is_ok() => qmove,
+            _ => {
+                illegal += 1;
+                continue;
+            }
+        };
+        let value = table.move_value(&game, qmove);
+        if value.map(Value::rank) == Some(expected.rank()) {
+            optimal += 1;
+        }
+        let same_outcome = match (value, expected) {
+            (Some(Value::Win(_)), Value::Win(_)) => true,
+            (Some(Value::Draw), Value::Draw) => true,
+            (Some(_), Value::Loss(_)) => true,
+            _ => false,
+        };
+        if same_outcome {
+            preserved += 1;
+        }
+    }
+
+    println!(
+        "{} on {} positions: {} optimal, {} kept the result, {} illegal or missing",
+        spec, samples, optimal, preserved, illegal
+    );
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut size = None;
+    let mut options = Options {
+        out: None,
+        check: None,
+        samples: 200,
+        seed: rng::time_seed(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("Missing value for {}\n{}", arg, USAGE));
+        match &arg[..] {
+            "--out" => options.out = Some(value()?),
+            "--check" => options.check = Some(value()?),
+            "--samples" => options.samples = value()?.parse()?,
+            "--seed" => options.seed = value()?.parse()?,
+            _ => size = Some(arg),
+        }
+    }
+
+    match size.as_ref().map(|s| &s[..]) {
+        Some("3") => run::<Quoridor3>(&options),
+        Some("4") => run::<Quoridor4>(&options),
+        Some("5") => run::<Quoridor5>(&options),
+        _ => Err(USAGE.into()),
+    }
+}
@@ -0,0 +1,265 @@
+use crossbeam_channel::unbounded;
+use quoridor_core::{ai::*, record::GameRecord, rulebooks::*, *};
+use quoridor_tools::{bots::*, stats::*};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tbmp::*;
+
+const USAGE: &str = "Usage: tournament <BOT_A> <BOT_B> [--games N] [--threads N] [--out FILE] [--max-plies N] [--seed N] [--sprt ELO0 ELO1] [--book FILE]
+Bots: mcts[:playouts], ab[:depth], <beginner|easy|medium|hard|expert>[:balanced|aggressive|racing]";
+
+struct Options {
+    bots: [String; 2],
+    games: usize,
+    threads: usize,
+    out: String,
+    max_plies: usize,
+    seed: u64,
+    sprt: Option<Sprt>,
+    book: Option<Arc<OpeningBook>>,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, Box<dyn Error>> {
+    let mut bots = vec![];
+    let mut options = Options {
+        bots: Default::default(),
+        games: 100,
+        threads: 4,
+        out: "tournament.jsonl".into(),
+        max_plies: 400,
+        seed: rng::time_seed(),
+        sprt: None,
+        book: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match &arg[..] {
+            "--games" => options.games = value()?.parse()?,
+            "--threads" => options.threads = value()?.parse()?,
+            "--out" => options.out = value()?.clone(),
+            "--max-plies" => options.max_plies = value()?.parse()?,
+            "--seed" => options.seed = value()?.parse()?,
+            "--sprt" => {
+                let elo0 = value()?.parse()?;
+                let elo1 = value()?.parse()?;
+                options.sprt = Some(Sprt::new(elo0, elo1));
+            }
+            "--book" => options.book = Some(load_book(value()?)?),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg).into()),
+            _ => bots.push(arg.clone()),
+        }
+    }
+
+    if bots.len() != 2 {
+        return Err("Expected exactly two bots".into());
+    }
+    options.bots = [bots.remove(0), bots.remove(0)];
+    Ok(options)
+}
+
+// Bot A moves first in even-numbered games
+fn play_game(
+    index: usize,
+    options: &Options,
+) -> Result<(GameRecord, [usize; 2]), Box<dyn Error>> {
+    let order = if index % 2 == 0 { [0, 1] } else { [1, 0] };
+    let mut seeds = rng::SplitMix64::new(options.seed.wrapping_add(index as u64));
+
+    let (mut cores, mut game_fn) = tbmp::new_game::<QGame<StandardQuoridor>>();
+    let mut record = GameRecord::new(
+        "standard",
+        order.iter().map(|&bot| options.bots[bot].clone()).collect(),
+    );
+
+    // Player 0's events pass through here so the moves can be recorded
+    let observed = cores.remove(0);
+    let (events, proxy_events) = unbounded();
+    let proxy = AgentCore {
+        event_channel: proxy_events,
+        move_channel: observed.move_channel.clone(),
+    };
+
+    let mut agents = vec![];
+    for (core, &bot) in std::iter::once(proxy).chain(cores).zip(order.iter()) {
+        let mut bot = parse_bot::<StandardQuoridor>(&options.bots[bot], seeds.next_u64())?;
+        if let Some(book) = &options.book {
+            bot = Box::new(BookBot::new(Arc::clone(book), bot, seeds.next_u64()));
+        }
+        agents.push(bot_agent(core, bot));
+    }
+
+    loop {
+        let result = game_fn()?;
+        while let Ok(event) = observed.event_channel.try_recv() {
+            if let GameEvent::MoveHappened(qmove) = &event {
+                record.moves.push(*qmove);
+            }
+            events.send(event).map_err(|_| "agent closed")?;
+        }
+        for agent in agents.iter_mut() {
+            agent()?;
+        }
+
+        match result {
+            MoveResult::Win(id) => {
+                record.winner = Some(id);
+                break;
+            }
+            MoveResult::Continue if record.moves.len() < options.max_plies => {}
+            _ => break,
+        }
+    }
+
+    Ok((record, order))
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let options = match parse_options(&args) {
+        Ok(options) => Arc::new(options),
+        Err(e) => {
+            println!("{}\n{}", e, USAGE);
+            return;
+        }
+    };
+
+    let mut out = match File::create(&options.out) {
+        Ok(file) => BufWriter::new(file),
+        Err(e) => {
+            println!("Could not create {}: {}", options.out, e);
+            return;
+        }
+    };
+
+    let next = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (results, finished) = unbounded();
+
+    let workers = (0..options.threads.max(1))
+        .map(|_| {
+            let (options, next, stop, results) = (
+                Arc::clone(&options),
+                Arc::clone(&next),
+                Arc::clone(&stop),
+                results.clone(),
+            );
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= options.games {
+                        break;
+                    }
+                    let result = play_game(index, &options).map_err(|e| e.to_string());
+                    if results.send(result).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(results);
+
+    let mut score = Score::default();
+    let mut first_score = Score::default();
+    let mut sprt_status = SprtStatus::Continue;
+
+    for result in finished.iter() {
+        let (record, order) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                println!("Game failed: {}", e);
+                continue;
+            }
+        };
+
+        let outcome = match record.winner {
+            Some(id) if order[id as usize] == 0 => Outcome::Win,
+            Some(_) => Outcome::Loss,
+            None => Outcome::Draw,
+        };
+        score.add(outcome);
+        if order[0] == 0 {
+            first_score.add(outcome);
+        }
+
+        if let Err(e) = serde_json::to_string(&record)
+            .map_err(|e| Box::new(e) as Box<dyn Error>)
+            .and_then(|line| Ok(writeln!(out, "{}", line)?))
+        {
+            println!("Could not write record: {}", e);
+        }
+
+        println!(
+            "Game {}/{}: +{} ={} -{} ({:+.1} Elo)",
+            score.games(),
+            options.games,
+            score.wins,
+            score.draws,
+            score.losses,
+            score.elo()
+        );
+
+        if let Some(sprt) = options.sprt {
+            sprt_status = sprt.status(&score);
+            if sprt_status != SprtStatus::Continue {
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    for worker in workers {
+        worker.join().ok();
+    }
+    out.flush().ok();
+
+    let (low, high) = score.elo_interval(1.96);
+    println!();
+    println!("{} vs {}", options.bots[0], options.bots[1]);
+    println!(
+        "Games: {}, +{} ={} -{}",
+        score.games(),
+        score.wins,
+        score.draws,
+        score.losses
+    );
+    println!(
+        "Score: {:.1}% (moving first {:.1}%, moving second {:.1}%)",
+        score.mean() * 100.0,
+        first_score.mean() * 100.0,
+        Score {
+            wins: score.wins - first_score.wins,
+            draws: score.draws - first_score.draws,
+            losses: score.losses - first_score.losses,
+        }
+        .mean()
+            * 100.0
+    );
+    println!(
+        "Elo difference: {:+.1} (95% interval {:+.1} to {:+.1})",
+        score.elo(),
+        low,
+        high
+    );
+    if let Some(sprt) = options.sprt {
+        let (lower, upper) = sprt.bounds();
+        println!(
+            "SPRT [{}, {}]: LLR {:.2} ({:.2}, {:.2}) -> {}",
+            sprt.elo0,
+            sprt.elo1,
+            sprt.llr(&score),
+            lower,
+            upper,
+            match sprt_status {
+                SprtStatus::Continue => "inconclusive",
+                SprtStatus::AcceptH0 => "H0 accepted",
+                SprtStatus::AcceptH1 => "H1 accepted",
+            }
+        );
+    }
+    println!("Records written to {}", options.out);
+}
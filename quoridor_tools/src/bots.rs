@@ -0,0 +1,42 @@
+use quoridor_core::{ai::*, *};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+// Accepted specs: "mcts[:playouts]", "ab[:depth]" or a difficulty with an optional style, e.g. "hard:racing"
+pub fn parse_bot<Rb: Rulebook<Move = Move>>(
+    spec: &str,
+    seed: u64,
+) -> Result<Box<dyn Bot<Rb>>, Box<dyn Error>> {
+    let mut parts = spec.splitn(2, ':');
+    let kind = parts.next().unwrap_or("");
+    let arg = parts.next();
+
+    Ok(match kind {
+        "mcts" => {
+            let mut config = MctsConfig::default();
+            if let Some(playouts) = arg {
+                config.playouts = playouts.parse()?;
+            }
+            Box::new(Mcts::new(config, seed))
+        }
+        "ab" => {
+            let limits = SearchLimits {
+                max_depth: arg.map_or(Ok(3), |depth| depth.parse())?,
+                time_limit: None,
+                node_limit: None,
+            };
+            Box::new(AlphaBeta::<Rb>::new(limits))
+        }
+        _ => {
+            let config = BotConfig::parse(kind, arg, seed)
+                .map_err(|_| format!("Unknown bot \"{}\"", spec))?;
+            Box::new(config.build::<Rb>())
+        }
+    })
+}
+
+pub fn load_book(path: &str) -> Result<Arc<OpeningBook>, Box<dyn Error>> {
+    Ok(Arc::new(serde_json::from_reader(BufReader::new(File::open(path)?))?))
+}
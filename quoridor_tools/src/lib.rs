@@ -0,0 +1,3 @@
+pub mod bots;
+pub mod npy;
+pub mod stats;
@@ -0,0 +1,107 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// Minimal writer for version 1.0 .npy files, readable with numpy.load
+
+pub trait NpyElement: Copy {
+    const DESCR: &'static str;
+    fn write_le(self, out: &mut dyn Write) -> std::io::Result<()>;
+}
+
+impl NpyElement for f32 {
+    const DESCR: &'static str = "<f4";
+    fn write_le(self, out: &mut dyn Write) -> std::io::Result<()> {
+        out.write_all(&self.to_le_bytes())
+    }
+}
+
+impl NpyElement for i64 {
+    const DESCR: &'static str = "<i8";
+    fn write_le(self, out: &mut dyn Write) -> std::io::Result<()> {
+        out.write_all(&self.to_le_bytes())
+    }
+}
+
+impl NpyElement for bool {
+    const DESCR: &'static str = "|b1";
+    fn write_le(self, out: &mut dyn Write) -> std::io::Result<()> {
+        out.write_all(&[self as u8])
+    }
+}
+
+pub fn write_npy<T: NpyElement>(path: &str, shape: &[usize], data: &[T]) -> Result<(), Box<dyn Error>> {
+    if shape.iter().product::<usize>() != data.len() {
+        return Err(format!("{}: shape {:?} does not match {} values", path, shape, data.len()).into());
+    }
+    let mut out = BufWriter::new(File::create(path)?);
+    write_array(&mut out, shape, data)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn write_array<T: NpyElement>(out: &mut dyn Write, shape: &[usize], data: &[T]) -> std::io::Result<()> {
+
+    let shape = match shape {
+        [n] => format!("({},)", n),
+        _ => format!(
+            "({})",
+            shape.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        T::DESCR,
+        shape
+    );
+    // Magic, version and length take 10 bytes; the header pads the data start to a multiple of 64
+    let padding = 63 - (10 + header.len()) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    out.write_all(b"\x93NUMPY\x01\x00")?;
+    out.write_all(&(header.len() as u16).to_le_bytes())?;
+    out.write_all(header.as_bytes())?;
+    for &value in data {
+        value.write_le(out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn array<T: NpyElement>(shape: &[usize], data: &[T]) -> Vec<u8> {
+        let mut out = vec![];
+        write_array(&mut out, shape, data).unwrap();
+        out
+    }
+
+    #[test]
+    fn data_starts_on_a_64_byte_boundary() {
+        for shape in [vec![3], vec![2, 3], vec![1000, 6, 9, 9]].iter() {
+            let data = vec![0.0f32; shape.iter().product()];
+            let bytes = array(shape, &data);
+            assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+
+            let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+            assert_eq!((10 + header_len) % 64, 0);
+            assert_eq!(bytes[10 + header_len - 1], b'\n');
+            assert_eq!(bytes.len(), 10 + header_len + 4 * data.len());
+        }
+    }
+
+    #[test]
+    fn headers_describe_the_array() {
+        let bytes = array(&[3], &[true, false, true]);
+        let header = String::from_utf8_lossy(&bytes[10..]);
+        assert!(header.starts_with("{'descr': '|b1', 'fortran_order': False, 'shape': (3,), }"));
+        assert_eq!(&bytes[bytes.len() - 3..], &[1, 0, 1]);
+
+        let bytes = array(&[2, 2], &[1i64, 2, 3, 4]);
+        assert!(String::from_utf8_lossy(&bytes[10..]).contains("'<i8'"));
+        assert!(String::from_utf8_lossy(&bytes[10..]).contains("(2, 2)"));
+        assert!(write_npy("unused.npy", &[2, 3], &[0.0f32; 4]).is_err());
+    }
+}
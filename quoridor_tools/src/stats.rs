@@ -0,0 +1,177 @@
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+pub fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+pub fn score_to_elo(score: f64) -> f64 {
+    // Clamped so a clean sweep reports a large but finite difference
+    let score = score.max(1e-4).min(1.0 - 1e-4);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+impl Score {
+    pub fn add(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Win => self.wins += 1,
+            Outcome::Draw => self.draws += 1,
+            Outcome::Loss => self.losses += 1,
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.games() == 0 {
+            0.5
+        } else {
+            (self.wins as f64 + self.draws as f64 * 0.5) / self.games() as f64
+        }
+    }
+
+    // Per-game variance of the score
+    pub fn variance(&self) -> f64 {
+        let n = self.games() as f64;
+        if n == 0.0 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        (self.wins as f64 * (1.0 - mean).powi(2)
+            + self.draws as f64 * (0.5 - mean).powi(2)
+            + self.losses as f64 * mean.powi(2))
+            / n
+    }
+
+    pub fn elo(&self) -> f64 {
+        score_to_elo(self.mean())
+    }
+
+    // Elo bounds for the given normal quantile, e.g. 1.96 for 95%
+    pub fn elo_interval(&self, z: f64) -> (f64, f64) {
+        let error = (self.variance() / self.games().max(1) as f64).sqrt();
+        (
+            score_to_elo(self.mean() - z * error),
+            score_to_elo(self.mean() + z * error),
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SprtStatus {
+    Continue,
+    AcceptH0,
+    AcceptH1,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Sprt {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    // Log-likelihood ratio under the usual normal approximation of the score
+    pub fn llr(&self, score: &Score) -> f64 {
+        let variance = score.variance();
+        if score.games() == 0 || variance == 0.0 {
+            return 0.0;
+        }
+        let s0 = elo_to_score(self.elo0);
+        let s1 = elo_to_score(self.elo1);
+        score.games() as f64 * (s1 - s0) * (2.0 * score.mean() - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn status(&self, score: &Score) -> SprtStatus {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtStatus::AcceptH1
+        } else if llr <= lower {
+            SprtStatus::AcceptH0
+        } else {
+            SprtStatus::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    fn score(wins: u32, draws: u32, losses: u32) -> Score {
+        Score {
+            wins,
+            draws,
+            losses,
+        }
+    }
+
+    #[test]
+    fn even_scores_are_zero_elo() {
+        assert!(close(score(5, 0, 5).elo(), 0.0));
+        assert!(close(score(0, 8, 0).elo(), 0.0));
+        assert!(close(score_to_elo(0.75), 190.849));
+        assert!(close(elo_to_score(score_to_elo(0.3)), 0.3));
+        assert!(score(10, 0, 0).elo().is_finite());
+    }
+
+    #[test]
+    fn intervals_surround_the_estimate() {
+        let (low, high) = score(30, 0, 30).elo_interval(1.96);
+        assert!(low < 0.0 && high > 0.0);
+        assert!(close(low, -high));
+
+        let wide = score(3, 0, 3).elo_interval(1.96);
+        assert!(wide.1 - wide.0 > high - low);
+    }
+
+    #[test]
+    fn sprt_bounds_and_decisions() {
+        let sprt = Sprt::new(0.0, 20.0);
+        let (lower, upper) = sprt.bounds();
+        assert!(close(lower, -(19f64.ln())));
+        assert!(close(upper, 19f64.ln()));
+
+        assert!(close(sprt.llr(&score(80, 0, 20)), 5.1324));
+        assert_eq!(sprt.status(&score(80, 0, 20)), SprtStatus::AcceptH1);
+        assert_eq!(sprt.status(&score(20, 0, 80)), SprtStatus::AcceptH0);
+        assert_eq!(sprt.status(&score(5, 0, 5)), SprtStatus::Continue);
+        assert_eq!(sprt.status(&Score::default()), SprtStatus::Continue);
+    }
+}
@@ -1,7 +1,11 @@
 use bimap::BiMap;
 use crossbeam_channel::{Receiver, Sender};
 use quoridor_core::{rulebooks::*, *};
-use std::{cell::RefCell, error::Error, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+    rc::Rc,
+};
 use tbmp_core::*;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
@@ -27,6 +31,10 @@ generate_rulebook! {
     [NO CONNECT]
     FreeQuoridor,
     StandardQuoridor,
+    MovingWallsQuoridor,
+    RandomSetupQuoridor,
+    ToroidalQuoridor,
+    FogQuoridor,
 }
 
 const STANDARD_CANVAS_SIZE: f64 = 150.0;
@@ -42,6 +50,9 @@ thread_local! {
             wall_slot: JsValue::from_str("#743c0d"),
             wall: JsValue::from_str("#996F38"),
             select: JsValue::from_str("#ACACAC"),
+            seam: JsValue::from_str("#C89B3C"),
+            fog: JsValue::from_str("rgba(0, 0, 0, 0.45)"),
+            hint: JsValue::from_str("rgba(60, 200, 120, 0.6)"),
             pawns: vec![]
         }
     );
@@ -71,6 +82,8 @@ fn set_colors(new_colors: ColorStruct) {
 struct State {
     highlight: Option<Position>,
     draw_start: Option<Wall>,
+    selected_wall: Option<Wall>,
+    hint: Option<ai::MoveAnalysis>,
 }
 
 #[derive(Clone)]
@@ -79,6 +92,9 @@ struct ColorStruct {
     wall_slot: JsValue,
     wall: JsValue,
     select: JsValue,
+    seam: JsValue,
+    fog: JsValue,
+    hint: JsValue,
     pawns: Vec<JsValue>,
 }
 
@@ -139,11 +155,8 @@ fn main() -> Option<()> {
     }
 
     let location = web_sys::window()?.location();
-    let href: String = location.href().ok()?;
-    let keys: Vec<_> = href.split('/').rev().filter(|s| !s.is_empty()).collect();
-    let game_name = keys[0];
-    let host = location.host().ok()?;
-    let mut ws = WebSocket::new(&format!("ws://{}/join/{}", host, game_name)).ok()?;
+    let pathname: String = location.pathname().ok()?;
+    let keys: Vec<_> = pathname.split('/').rev().filter(|s| !s.is_empty()).collect();
 
     let context = canvas
         .get_context("2d")
@@ -151,10 +164,48 @@ fn main() -> Option<()> {
         .dyn_into::<web_sys::CanvasRenderingContext2d>()
         .ok()?;
 
+    if keys.get(2) == Some(&"offline") {
+        let search: String = location.search().ok()?;
+        let style = search
+            .trim_start_matches('?')
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("style="));
+        match start_offline(keys[1], keys[0], style) {
+            Some(agent) => rec(agent, context, data_div, size, canvas),
+            None => {
+                alert!("Unknown game type or bot level");
+            }
+        }
+        return Some(());
+    }
+
+    let game_name = keys[0];
+    let host = location.host().ok()?;
+    // A token in session storage means this tab already holds a seat, e.g. after a reload
+    let session_key = format!("quoridor-session:{}", game_name);
+    let storage = web_sys::window()?.session_storage().ok()??;
+    let url = match storage.get_item(&session_key).ok()? {
+        Some(token) => format!("ws://{}/resume/{}", host, token),
+        None => format!("ws://{}/join/{}", host, game_name),
+    };
+    let mut ws = WebSocket::new(&url).ok()?;
+
     let agent = match &keys[1][..] {
-        "free" => QAgent::FreeQuoridor(WSAgent::<QGame<FreeQuoridor>>::connect(&mut ws)),
+        "free" => QAgent::FreeQuoridor(WSAgent::<QGame<FreeQuoridor>>::connect(&mut ws, &session_key)),
         "standard" => {
-            QAgent::StandardQuoridor(WSAgent::<QGame<StandardQuoridor>>::connect(&mut ws))
+            QAgent::StandardQuoridor(WSAgent::<QGame<StandardQuoridor>>::connect(&mut ws, &session_key))
+        }
+        "moving" => {
+            QAgent::MovingWallsQuoridor(WSAgent::<QGame<MovingWallsQuoridor>>::connect(&mut ws, &session_key))
+        }
+        "fog" => {
+            QAgent::FogQuoridor(WSAgent::<QGame<FogQuoridor>>::connect(&mut ws, &session_key))
+        }
+        "torus" => {
+            QAgent::ToroidalQuoridor(WSAgent::<QGame<ToroidalQuoridor>>::connect(&mut ws, &session_key))
+        }
+        "random" => {
+            QAgent::RandomSetupQuoridor(WSAgent::<QGame<RandomSetupQuoridor>>::connect(&mut ws, &session_key))
         }
         _ => panic!(),
     };
@@ -208,6 +259,51 @@ fn main() -> Option<()> {
     Some(())
 }
 
+// Plays against a bot running in the page; there is no clock on wasm, so the bot searches to a node budget
+fn start_offline(game_type: &str, level: &str, style: Option<&str>) -> Option<QAgent> {
+    let game_type = match game_type {
+        "free" => QGameType::FreeQuoridor,
+        "standard" => QGameType::StandardQuoridor,
+        "moving" => QGameType::MovingWallsQuoridor,
+        "fog" => QGameType::FogQuoridor,
+        "torus" => QGameType::ToroidalQuoridor,
+        "random" => QGameType::RandomSetupQuoridor,
+        _ => return None,
+    };
+    let seed = (js_sys::Math::random() * u64::MAX as f64) as u64;
+    let config = ai::BotConfig::parse(level, style, seed).ok()?.depth_only();
+
+    let (mut agents, mut game_fn) = game_type.new_game();
+    let mut bot = agents.pop()?.bot(config);
+    let agent = agents.remove(0);
+
+    // The bot answers a tick after the move it replies to, so the board is drawn before it thinks
+    let mut running = true;
+    let step = move || {
+        if !running {
+            return;
+        }
+        let result = bot().and_then(|_| game_fn());
+        match result {
+            Ok(MoveResult::Continue) => {}
+            Ok(_) => running = false,
+            Err(e) => {
+                console_log!("offline game stopped: {}", e);
+                running = false;
+            }
+        }
+    };
+    let closure = Closure::wrap(Box::new(step) as Box<dyn FnMut()>);
+    web_sys::window()?
+        .set_interval_with_callback_and_timeout_and_arguments_0(
+            closure.as_ref().unchecked_ref(),
+            100,
+        )
+        .ok()?;
+    closure.forget();
+    Some(agent)
+}
+
 fn  get_coords_from_event(e: &web_sys::PointerEvent, side: PlayerID) -> (f64, f64) {
     // e = Mouse click event.
     let rect = e
@@ -245,7 +341,7 @@ fn on_connect(
     set_colors(colors);
 
     let state = State::default();
-    render_game(&context, &data_div, &game, &state);
+    render_game(&context, &data_div, &game, &state, side);
 
     let rc = Rc::new((
         RefCell::new(game),
@@ -296,6 +392,7 @@ fn on_connect(
             }
             (false, false) => {
                 if event.pointer_type() == "mouse" {
+                    let x = if game.is_toroidal() { x % 9 } else { x };
                     let wall = Wall {
                         position: (x, 9 - y).into(),
                         orientation: if event.button() == 0 {
@@ -306,10 +403,29 @@ fn on_connect(
                         wall_type: WallType::Simple,
                     };
 
-                    if game.turn_of() == *side {
-                        //send a move
-                        let qmv = Move::PlaceWall(wall);
-                        agent.send_move(RulebookMove::wrap(&game, &qmv)).unwrap();
+                    // Only rulebooks that move walls let a click pick up one of the player's own walls
+                    let owned = game.moves_walls() && game.wall_owners().get(&wall) == Some(&*side);
+                    let qmv = match state.selected_wall {
+                        Some(selected) if selected == wall => {
+                            state.selected_wall = None;
+                            Some(Move::RemoveWall(wall))
+                        }
+                        _ if owned => {
+                            state.selected_wall = Some(wall);
+                            None
+                        }
+                        Some(selected) => {
+                            state.selected_wall = None;
+                            Some(Move::MoveWall(selected, wall))
+                        }
+                        None => Some(Move::PlaceWall(wall)),
+                    };
+
+                    if let Some(qmv) = qmv {
+                        if game.turn_of() == *side {
+                            //send a move
+                            agent.send_move(RulebookMove::wrap(&game, &qmv)).unwrap();
+                        }
                     }
                 }
             }
@@ -326,15 +442,15 @@ fn on_connect(
                 state.draw_start = Some(w1);
             }
         }
-        render_game(&context, &data_div, &game, &state);
+        render_game(&context, &data_div, &game, &state, *side);
     };
 
     let rcc = Clone::clone(&rc);
     let game_event_handler = move || {
         let mut game = rcc.0.borrow_mut();
         let context = rcc.1.borrow_mut();
-        let state = rcc.2.borrow();
-        //let side = rcc.3.borrow();
+        let mut state = rcc.2.borrow_mut();
+        let side = rcc.3.borrow();
         let agent = rcc.4.borrow();
         let div = rcc.5.borrow();
 
@@ -342,6 +458,12 @@ fn on_connect(
             match e {
                 QGameEvent::MoveHappened(qmv) => {
                     game.apply_move(&qmv);
+                    state.hint = None;
+                }
+                QGameEvent::GameStart(g, _) => {
+                    // Hidden-information games resend the visible state after every move
+                    *game = g;
+                    state.hint = None;
                 }
                 QGameEvent::GameEnd(pid) => {
                     if let Some(id) = pid {
@@ -355,7 +477,7 @@ fn on_connect(
                 }
                 _ => {}
             }
-            render_game(&context, &div, &game, &state);
+            render_game(&context, &div, &game, &state, *side);
         }
     };
 
@@ -401,10 +523,12 @@ fn on_connect(
                         console_log!("{:?} {:?}", w1, w2);
 
                         if delta_x.abs() + delta_y.abs() == 1 {
+                            let x = u8::max(x1, x2);
+                            let x = if game.is_toroidal() { x % 9 } else { x };
                             let wall = Wall {
                                 wall_type: WallType::Simple,
                                 orientation: w1.orientation,
-                                position: (u8::max(x1, x2), u8::max(y1, y2)).into(),
+                                position: (x, u8::max(y1, y2)).into(),
                             };
 
                             let qmv = Move::PlaceWall(wall);
@@ -416,6 +540,33 @@ fn on_connect(
         }
     };
 
+    let rcc = Clone::clone(&rc);
+    let on_key_down = move |event: web_sys::KeyboardEvent| {
+        if event.key() != "h" {
+            return;
+        }
+        let game = rcc.0.borrow();
+        let context = rcc.1.borrow();
+        let mut state = rcc.2.borrow_mut();
+        let side = rcc.3.borrow();
+        let div = rcc.5.borrow();
+
+        if game.turn_of() == *side {
+            // Deadlines need a clock, which wasm doesn't have, so the search is bounded by nodes instead
+            let limits = ai::SearchLimits {
+                max_depth: 2,
+                time_limit: None,
+                node_limit: Some(ai::DEPTH_ONLY_NODES),
+            };
+            state.hint = game.analyse(1, limits).into_iter().next();
+            render_game(&context, &div, &game, &state, *side);
+        }
+    };
+
+    let closure = Closure::wrap(Box::new(on_key_down) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+    web_sys::window().unwrap().set_onkeydown(Some(closure.as_ref().unchecked_ref()));
+    closure.forget();
+
     let closure = Closure::wrap(Box::new(game_event_handler) as Box<dyn FnMut()>);
     let window = web_sys::window().unwrap();
     window
@@ -440,6 +591,7 @@ fn render_game(
     data_div: &web_sys::HtmlElement,
     game: &Quoridor,
     state: &State,
+    side: PlayerID,
 ) {
     context.set_fill_style(&get_colors().base);
     context.fill_rect(0.0, 0.0, STANDARD_CANVAS_SIZE, STANDARD_CANVAS_SIZE);
@@ -462,27 +614,67 @@ fn render_game(
         );
     }
 
-    context.set_fill_style(&colors.wall);
+    if game.is_toroidal() {
+        // The outermost vertical slots are the same seam, drawn on both edges
+        context.set_fill_style(&colors.seam);
+        context.fill_rect(0.0, 0.0, WALL_WIDTH, STANDARD_CANVAS_SIZE);
+        context.fill_rect(9.0 * UNIT_WIDTH, 0.0, WALL_WIDTH, STANDARD_CANVAS_SIZE);
+    }
+
     for wall in game.walls().iter() {
-        let h_control = (wall.orientation == Orientation::Horizontal) as u8 as f64;
-        let v_conrol = (wall.orientation == Orientation::Vertical) as u8 as f64;
+        if state.selected_wall == Some(*wall) {
+            context.set_fill_style(&colors.select);
+        } else {
+            context.set_fill_style(&colors.wall);
+        }
 
         match wall.wall_type {
             WallType::Simple => {
-                let x = wall.position.x as f64 * UNIT_WIDTH - h_control * SPOT_WIDTH;
-                let y = (9 - wall.position.y) as f64 * UNIT_WIDTH - v_conrol * SPOT_WIDTH;
-                context.fill_rect(
-                    x,
-                    y,
-                    WALL_WIDTH * v_conrol + (UNIT_WIDTH + SPOT_WIDTH) * h_control,
-                    WALL_WIDTH * h_control + (UNIT_WIDTH + SPOT_WIDTH) * v_conrol,
-                );
+                let (x, y, w, h) = wall_rect(wall);
+                context.fill_rect(x, y, w, h);
+                if game.is_toroidal() && wall.position.x == 0 {
+                    // Walls on the seam continue on the opposite edge
+                    context.fill_rect(x + 9.0 * UNIT_WIDTH, y, w, h);
+                }
             }
             WallType::Single => unimplemented!("Can't render single walls! ( Yet ;) )"),
             WallType::Strong => unimplemented!("Can't strong single walls! ( Yet ;) )"),
         }
     }
 
+    if let Quoridor::FogQuoridor(g) = game {
+        context.set_fill_style(&colors.fog);
+        for x in 0..9 {
+            for y in 0..9 {
+                if !FogQuoridor::cell_visible(g, side, (x, y).into()) {
+                    context.fill_rect(
+                        WALL_WIDTH + x as f64 * UNIT_WIDTH,
+                        WALL_WIDTH + (8 - y) as f64 * UNIT_WIDTH,
+                        SPOT_WIDTH,
+                        SPOT_WIDTH,
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(hint) = &state.hint {
+        context.set_fill_style(&colors.hint);
+        match hint.qmove {
+            Move::MovePawn(_, target) => context.fill_rect(
+                WALL_WIDTH + target.x as f64 * UNIT_WIDTH,
+                WALL_WIDTH + (8 - target.y) as f64 * UNIT_WIDTH,
+                SPOT_WIDTH,
+                SPOT_WIDTH,
+            ),
+            Move::PlaceWall(wall) | Move::MoveWall(_, wall) => {
+                let (x, y, w, h) = wall_rect(&wall);
+                context.fill_rect(x, y, w, h);
+            }
+            Move::RemoveWall(_) => {}
+        }
+    }
+
     for (&id, &pos) in game.pawns().iter() {
         let (x, y) = (pos.x as f64, (8 - pos.y) as f64);
 
@@ -501,11 +693,24 @@ fn render_game(
         );
     }
 
-    data_div.set_inner_html(
-        &game
-            .wall_counts()
-            .fold(String::from("Walls left ->"), |f, i| f + &format!(" Player {},", i) + &i.to_string()),
-    );
+    let mut text = game
+        .wall_counts()
+        .fold(String::from("Walls left ->"), |f, i| f + &format!(" Player {},", i) + &i.to_string());
+    if let Some(hint) = &state.hint {
+        text += &format!("<br>Hint: {:?} (score {}), expected line: {:?}", hint.qmove, hint.score, hint.line);
+    }
+    data_div.set_inner_html(&text);
+}
+
+fn wall_rect(wall: &Wall) -> (f64, f64, f64, f64) {
+    let h_control = (wall.orientation == Orientation::Horizontal) as u8 as f64;
+    let v_conrol = (wall.orientation == Orientation::Vertical) as u8 as f64;
+
+    let x = wall.position.x as f64 * UNIT_WIDTH - h_control * SPOT_WIDTH;
+    let y = (9 - wall.position.y) as f64 * UNIT_WIDTH - v_conrol * SPOT_WIDTH;
+    let w = WALL_WIDTH * v_conrol + (UNIT_WIDTH + SPOT_WIDTH) * h_control;
+    let h = WALL_WIDTH * h_control + (UNIT_WIDTH + SPOT_WIDTH) * v_conrol;
+    (x, y, w, h)
 }
 
 trait PID {
@@ -521,21 +726,37 @@ impl PID for PawnID {
 }
 
 trait WSAgent<G: Game> {
-    fn connect(&mut self) -> AgentCore<G>;
+    fn connect(&mut self, session_key: &str) -> AgentCore<G>;
 }
 
 impl<G: Game> WSAgent<G> for WebSocket {
-    fn connect(&mut self) -> AgentCore<G> {
+    fn connect(&mut self, session_key: &str) -> AgentCore<G> {
         console_log!("connectin");
         let ws = self.clone();
         ws.set_binary_type(web_sys::BinaryType::Arraybuffer);
+        let storage = web_sys::window().unwrap().session_storage().unwrap().unwrap();
+        let resuming = storage.get_item(session_key).unwrap().is_some();
+        // Whether the server gave us a seat we can take back, and whether the game is over
+        let seated = Rc::new(Cell::new(false));
+        let over = Rc::new(Cell::new(false));
+
         let (etx, erx) = crossbeam_channel::unbounded();
+        let key = session_key.to_owned();
+        let (st, seated_msg, over_msg) = (storage.clone(), seated.clone(), over.clone());
         let onmessage_callback = Closure::wrap(Box::new(move |e: MessageEvent| {
             console_log!("RECEIVIN SHIT");
-            if let Ok(abuf) = e.data().dyn_into::<js_sys::ArrayBuffer>() {
+            if let Some(token) = e.data().as_string() {
+                // The session token comes first, as the only text message
+                st.set_item(&key, &token).unwrap();
+                seated_msg.set(true);
+            } else if let Ok(abuf) = e.data().dyn_into::<js_sys::ArrayBuffer>() {
                 console_log!("deserializin the shit");
                 let array = js_sys::Uint8Array::new(&abuf);
-                let event = bincode::deserialize(&array.to_vec()).unwrap();
+                let event: GameEvent<G> = bincode::deserialize(&array.to_vec()).unwrap();
+                if let GameEvent::GameEnd(_) = event {
+                    st.remove_item(&key).unwrap();
+                    over_msg.set(true);
+                }
                 console_log!("sent the shit");
                 etx.send(event).unwrap();
             }
@@ -545,6 +766,36 @@ impl<G: Game> WSAgent<G> for WebSocket {
         ws.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
         onmessage_callback.forget();
 
+        let key = session_key.to_owned();
+        let onclose_callback = Closure::wrap(Box::new(move || {
+            let window = web_sys::window().unwrap();
+            if over.get() {
+                return;
+            }
+            if seated.get() {
+                // Reloading resumes the seat with the stored token, so just try again shortly
+                let reload = Closure::once_into_js(|| {
+                    web_sys::window().unwrap().location().reload().ok();
+                });
+                window
+                    .set_timeout_with_callback_and_timeout_and_arguments_0(
+                        reload.unchecked_ref(),
+                        1000,
+                    )
+                    .unwrap();
+            } else {
+                storage.remove_item(&key).unwrap();
+                if resuming {
+                    // The seat was given up or the game is gone, so start over
+                    window.location().reload().ok();
+                } else {
+                    alert!("Lost connection to the game");
+                }
+            }
+        }) as Box<dyn FnMut()>);
+        ws.set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
+        onclose_callback.forget();
+
         let (mtx, mrx) = crossbeam_channel::unbounded();
 
         let cb = Closure::wrap(Box::new(move || {
@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, RwLock};
 use warp::{hyper::Uri, Filter};
 use warp::{
@@ -13,24 +14,51 @@ use warp::{
 };
 
 use bimap::BiMap;
-use crossbeam_channel::{Receiver, Sender};
-use quoridor_core::{rulebooks::*, *};
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use quoridor_core::{
+    archive::{self, Archive, Query},
+    net::{self, Follower, Timeouts},
+    record::GameRecord,
+    rulebooks::*,
+    *,
+};
 use std::error::Error;
 use tbmp::*;
 
 generate_rulebook! {
     StandardQuoridor,
     FreeQuoridor,
+    MovingWallsQuoridor,
+    RandomSetupQuoridor,
+    ToroidalQuoridor,
+    FogQuoridor,
 }
 
 type GameFn = Box<dyn Send + Sync + FnMut() -> Result<MoveResult, Box<dyn Error>>>;
-type Lobbies = Arc<RwLock<HashMap<String, (Vec<QAgent>, QGameType, GameFn)>>>;
-type Games = Arc<RwLock<HashMap<String, GameFn>>>;
+type ForfeitFn = Box<dyn Send + Sync + FnMut(Option<PlayerID>)>;
+type Lobbies = Arc<RwLock<HashMap<String, (Vec<QAgent>, QGameType, Table)>>>;
+type Games = Arc<RwLock<HashMap<String, Table>>>;
+type Sessions = Arc<RwLock<HashMap<String, Arc<dyn WSSeat>>>>;
+type WsSender = mpsc::UnboundedSender<Result<Message, warp::Error>>;
+
+// A game's step function, and what to do with the game if a player forfeits it. Steps can run a
+// bot's search, so they happen on a blocking thread with the step locked rather than the games.
+struct Table {
+    step: Arc<std::sync::Mutex<GameFn>>,
+    forfeit: ForfeitFn,
+}
+
+// A WebSocket that says nothing for this many pings is taken as dropped
+const MISSED_PINGS: u32 = 3;
 
 #[derive(Serialize, Deserialize)]
 struct LobbyRequest {
     game_type: String,
     name: String,
+    #[serde(default)]
+    bot: Option<String>,
+    #[serde(default)]
+    style: Option<String>,
 }
 
 macro_rules! warpify {
@@ -44,6 +72,37 @@ fn gtstr(gt: &QGameType) -> &'static str {
     match gt {
         QGameType::StandardQuoridor => "standard",
         QGameType::FreeQuoridor => "free",
+        QGameType::MovingWallsQuoridor => "moving",
+        QGameType::FogQuoridor => "fog",
+        QGameType::ToroidalQuoridor => "torus",
+        QGameType::RandomSetupQuoridor => "random",
+    }
+}
+
+fn archive_game(
+    archive: &Archive,
+    record: &std::sync::Mutex<GameRecord>,
+    options: Vec<(String, String)>,
+    started: u64,
+    winner: Option<PlayerID>,
+) {
+    let mut record = match record.lock() {
+        Ok(record) => record.clone(),
+        Err(_) => return,
+    };
+    record.winner = winner;
+    if let Err(e) = archive.store(record, options, started) {
+        eprintln!("Could not archive a game: {}", e);
+    }
+}
+
+fn env_seconds(name: &str) -> Result<Option<u64>, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} should be a number of seconds, not {}", name, value)),
+        Err(_) => Ok(None),
     }
 }
 
@@ -56,6 +115,8 @@ async fn get_lobbies(lobbies: Lobbies) -> Result<impl warp::Reply, Infallible> {
             .map(|(name, (_, game_type, _))| LobbyRequest {
                 game_type: gtstr(game_type).into(),
                 name: name.clone(),
+                bot: None,
+                style: None,
             })
             .collect::<Vec<_>>(),
     ))
@@ -69,16 +130,87 @@ async fn main() {
 
     let lobbies = Lobbies::default();
 
+    let sessions = Sessions::default();
+
+    // Finished games are kept here; QUORIDOR_ARCHIVE picks another directory
+    let archive_dir = std::env::var("QUORIDOR_ARCHIVE").unwrap_or_else(|_| "archive".into());
+    let archive = match Archive::open(archive_dir) {
+        Ok(archive) => archive,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    // QUORIDOR_HEARTBEAT and QUORIDOR_MOVE_TIME, in seconds, set how often players are pinged
+    // (0 for never) and how long they have for each move before forfeiting
+    let mut timeouts = Timeouts::default();
+    let configured = env_seconds("QUORIDOR_HEARTBEAT").and_then(|heartbeat| {
+        if let Some(seconds) = heartbeat {
+            timeouts.heartbeat = Some(Duration::from_secs(seconds)).filter(|_| seconds > 0);
+        }
+        timeouts.move_time = env_seconds("QUORIDOR_MOVE_TIME")?.map(Duration::from_secs);
+        Ok(())
+    });
+    if let Err(e) = configured {
+        eprintln!("{}", e);
+        return;
+    }
+
     let new_lobby = warp::post()
         .and(path!("lobby" / "new"))
         .and(parse_lobby_request())
         .and(warpify!(lobbies))
+        .and(warpify!(archive))
         .and_then(
-            |(game_type, name): (QGameType, String), lobbies: Lobbies| async move {
-                let (v, t) = game_type.new_game();
+            |(game_type, name, bot, options): (QGameType, String, Option<ai::BotConfig>, Vec<(String, String)>),
+             lobbies: Lobbies,
+             archive: Archive| async move {
+                // The record follows the whole board rather than a seat, and is archived once there is a result
+                let record = Arc::new(std::sync::Mutex::new(GameRecord::new(gtstr(&game_type), vec![])));
+                let (mut v, mut t) = game_type.new_recorded_game(record.clone());
+                let mut players = vec![String::from("web"); v.len()];
+                if let Some(config) = bot {
+                    // The bot takes the second seat and answers within the same game step as the human
+                    let mut bot = v.pop().unwrap().bot(config);
+                    let mut game_fn = t;
+                    t = Box::new(move || {
+                        let result = game_fn()?;
+                        bot()?;
+                        match result {
+                            MoveResult::Continue => game_fn(),
+                            result => Ok(result),
+                        }
+                    });
+                    *players.last_mut().unwrap() = "bot".into();
+                }
+
+                if let Ok(mut record) = record.lock() {
+                    record.players = players;
+                }
+                let started = archive::now();
+                let mut game_fn = t;
+                let (a, r, o) = (archive.clone(), record.clone(), options.clone());
+                let step: GameFn = Box::new(move || {
+                    let result = game_fn()?;
+                    match result {
+                        MoveResult::Continue => {}
+                        MoveResult::Win(id) => archive_game(&a, &r, o.clone(), started, Some(id)),
+                        MoveResult::Draw => archive_game(&a, &r, o.clone(), started, None),
+                    }
+                    Ok(result)
+                });
+                let forfeit: ForfeitFn = Box::new(move |winner| {
+                    archive_game(&archive, &record, options.clone(), started, winner)
+                });
+
                 let gt = game_type;
                 let n = name.clone();
-                lobbies.write().await.insert(name, (v, game_type, t));
+                let table = Table {
+                    step: Arc::new(std::sync::Mutex::new(step)),
+                    forfeit,
+                };
+                lobbies.write().await.insert(name, (v, game_type, table));
                 Ok::<_, std::convert::Infallible>(warp::redirect(
                     Uri::builder()
                         .path_and_query(&format!("/game/{}/{}", gtstr(&gt), n)[..])
@@ -93,13 +225,42 @@ async fn main() {
         .and(warpify!(lobbies))
         .and_then(get_lobbies);
 
+    // Past games, newest first, narrowed by ?rulebook=, ?player= and ?limit=
+    let archive_list = warp::get()
+        .and(path!("archive"))
+        .and(warp::query::<Query>())
+        .and(warpify!(archive))
+        .and_then(|query: Query, archive: Archive| async move {
+            archive
+                .list(&query)
+                .map(|games| warp::reply::json(&games))
+                .map_err(|_| warp::reject::not_found())
+        });
+
+    let archive_game = warp::get()
+        .and(path!("archive" / String))
+        .and(warpify!(archive))
+        .and_then(|id: String, archive: Archive| async move {
+            archive
+                .fetch(&id)
+                .map(|game| warp::reply::json(&game))
+                .map_err(|_| warp::reject::not_found())
+        });
+
     let join = warp::get()
         .and(path!("join" / String))
         .and(warpify!(lobbies))
         .and(warpify!(games))
+        .and(warpify!(sessions))
+        .and(warpify!(timeouts))
         .and(warp::ws())
         .map(
-            |name: String, lobbies: Lobbies, games: Games, socket: warp::ws::Ws| {
+            |name: String,
+             lobbies: Lobbies,
+             games: Games,
+             sessions: Sessions,
+             timeouts: Timeouts,
+             socket: warp::ws::Ws| {
                 socket.on_upgrade(|socket| async move {
                     let arc = Clone::clone(&lobbies);
                     let mut lobbies = lobbies.write().await;
@@ -112,8 +273,31 @@ async fn main() {
                         drop(lobbies);
                     }
                     match agent {
-                        QAgent::StandardQuoridor(c) => c.host(socket, games, arc, name),
-                        QAgent::FreeQuoridor(c) => c.host(socket, games, arc, name),
+                        QAgent::StandardQuoridor(c) => c.host(socket, games, arc, sessions, timeouts, name),
+                        QAgent::FreeQuoridor(c) => c.host(socket, games, arc, sessions, timeouts, name),
+                        QAgent::MovingWallsQuoridor(c) => c.host(socket, games, arc, sessions, timeouts, name),
+                        QAgent::FogQuoridor(c) => c.host(socket, games, arc, sessions, timeouts, name),
+                        QAgent::ToroidalQuoridor(c) => c.host(socket, games, arc, sessions, timeouts, name),
+                        QAgent::RandomSetupQuoridor(c) => c.host(socket, games, arc, sessions, timeouts, name),
+                    }
+                })
+            },
+        );
+
+    // Takes back a seat after a dropped connection. An unknown token just gets the socket closed,
+    // which the client takes as the game being gone.
+    let resume = warp::get()
+        .and(path!("resume" / String))
+        .and(warpify!(sessions))
+        .and(warpify!(games))
+        .and(warpify!(lobbies))
+        .and(warp::ws())
+        .map(
+            |token: String, sessions: Sessions, games: Games, lobbies: Lobbies, socket: warp::ws::Ws| {
+                socket.on_upgrade(|socket| async move {
+                    let seat = sessions.read().await.get(&token).cloned();
+                    if let Some(seat) = seat {
+                        seat.attach(socket, games, lobbies);
                     }
                 })
             },
@@ -123,6 +307,10 @@ async fn main() {
     let game = path!("game" / String / String)
         .and(warp::fs::file("./static/game.html"))
         .map(|_, _, f: warp::fs::File| f);
+    // Offline games run entirely in the client, so the page is all the server provides
+    let offline = path!("offline" / String / String)
+        .and(warp::fs::file("./static/game.html"))
+        .map(|_, _, f: warp::fs::File| f);
     //let index = warp::path::end().map(|| warp::reply::html(INDEX_HTML));
     let index = warp::path::end()
         .and(warp::fs::file("./static/index.html"))
@@ -132,9 +320,13 @@ async fn main() {
 
     let routes = index
         .or(game)
+        .or(offline)
         .or(lobby_list)
+        .or(archive_list)
+        .or(archive_game)
         .or(new_lobby)
         .or(join)
+        .or(resume)
         .or(path("static").and(
             warp::fs::dir("./static")
                 .map(|f: warp::fs::File| warp::reply::with_header(f, "name", "value")),
@@ -143,67 +335,309 @@ async fn main() {
     warp::serve(routes).run(([0, 0, 0, 0], 3030)).await;
 }
 
-fn parse_lobby_request() -> impl Filter<Extract = ((QGameType, String),), Error = Rejection> + Copy
-{
+fn parse_lobby_request() -> impl Filter<
+    Extract = ((QGameType, String, Option<ai::BotConfig>, Vec<(String, String)>),),
+    Error = Rejection,
+> + Copy {
     warp::body::form().and_then(|gt: LobbyRequest| async move {
         let game_type = match &gt.game_type[..] {
             "standard" => QGameType::StandardQuoridor,
             "free" => QGameType::FreeQuoridor,
+            "moving" => QGameType::MovingWallsQuoridor,
+            "fog" => QGameType::FogQuoridor,
+            "torus" => QGameType::ToroidalQuoridor,
+            "random" => QGameType::RandomSetupQuoridor,
             _ => return Err(warp::reject::custom(UnimplementedGameType)),
         };
 
-        Ok((game_type, gt.name))
+        let bot = match gt.bot.as_ref().filter(|bot| !bot.is_empty()) {
+            Some(difficulty) => Some(
+                ai::BotConfig::parse(difficulty, gt.style.as_ref().map(|s| &s[..]), rng::time_seed())
+                    .map_err(|_| warp::reject::custom(UnknownBotConfig))?,
+            ),
+            None => None,
+        };
+
+        // Recorded with archived games, the way the TCP server records its options
+        let options = match bot {
+            Some(_) => vec![("bot", gt.bot), ("style", gt.style)]
+                .into_iter()
+                .filter_map(|(key, value)| Some((key.to_string(), value.filter(|v| !v.is_empty())?)))
+                .collect(),
+            None => vec![],
+        };
+
+        Ok((game_type, gt.name, bot, options))
     })
 }
 
 trait WSHost {
-    fn host(self, socket: WebSocket, games: Games, lobbies: Lobbies, name: String);
+    fn host(
+        self,
+        socket: WebSocket,
+        games: Games,
+        lobbies: Lobbies,
+        sessions: Sessions,
+        timeouts: Timeouts,
+        name: String,
+    );
+}
+
+impl<G: Game> WSHost for AgentCore<G>
+where
+    G::Move: Copy,
+{
+    fn host(
+        self,
+        socket: WebSocket,
+        games: Games,
+        lobbies: Lobbies,
+        sessions: Sessions,
+        timeouts: Timeouts,
+        name: String,
+    ) {
+        let token = match net::session_token() {
+            Ok(token) => token,
+            Err(e) => {
+                eprintln!("Could not make a session token for {}: {}", name, e);
+                return;
+            }
+        };
+        let seat = Arc::new(WebSeat {
+            name: name.clone(),
+            token: token.clone(),
+            moves: self.move_channel,
+            timeouts,
+            state: std::sync::Mutex::new(SeatState {
+                follower: Follower::default(),
+                link: None,
+                connection: 0,
+                dropped: None,
+                heard: Instant::now(),
+                turn_since: None,
+            }),
+        });
+        seat.clone().attach(socket, games.clone(), lobbies.clone());
+
+        let ec = self.event_channel;
+        tokio::spawn(async move {
+            sessions.write().await.insert(token.clone(), seat.clone());
+            let mut last_ping = Instant::now();
+            loop {
+                match ec.try_recv() {
+                    Ok(event) => {
+                        let over = matches!(event, GameEvent::GameEnd(_));
+                        seat.forward(event);
+                        if over {
+                            break;
+                        }
+                    }
+                    Err(TryRecvError::Disconnected) => break,
+                    Err(TryRecvError::Empty) => {
+                        if let Some(interval) = timeouts.heartbeat {
+                            if last_ping.elapsed() >= interval {
+                                last_ping = Instant::now();
+                                seat.ping();
+                            }
+                        }
+                        let mut failed = seat.failed();
+                        // The clock only runs once everyone has joined
+                        if failed == Some("out of time") && !games.read().await.contains_key(&name) {
+                            seat.restart_clock();
+                            failed = None;
+                        }
+                        if let Some(reason) = failed {
+                            let by = seat.state.lock().unwrap().follower.seat();
+                            let winner = if G::PLAYER_COUNT == 2 { Some(1 - by) } else { None };
+                            eprintln!("Player {} forfeited {} ({})", by, name, reason);
+                            for other in sessions.read().await.values() {
+                                if other.game() == name {
+                                    other.forfeit(by, winner);
+                                }
+                            }
+                            if let Some(mut table) = games.write().await.remove(&name) {
+                                (table.forfeit)(winner);
+                            }
+                            lobbies.write().await.remove(&name);
+                            break;
+                        }
+                        tokio::task::yield_now().await;
+                    }
+                }
+            }
+            sessions.write().await.remove(&token);
+        });
+    }
+}
+
+struct SeatState<G: Game> {
+    follower: Follower<G>,
+    link: Option<WsSender>,
+    // Counts connections, so an old one closing late does not cut off its replacement
+    connection: u64,
+    dropped: Option<Instant>,
+    // When the player's connection last sent anything, pongs included
+    heard: Instant,
+    // When the game started waiting on this seat's move
+    turn_since: Option<Instant>,
+}
+
+// A player's seat, which outlives any one WebSocket so the player can reload or reconnect
+struct WebSeat<G: Game> {
+    name: String,
+    token: String,
+    moves: Sender<G::Move>,
+    timeouts: Timeouts,
+    state: std::sync::Mutex<SeatState<G>>,
+}
+
+impl<G: Game> WebSeat<G>
+where
+    G::Move: Copy,
+{
+    fn forward(&self, event: GameEvent<G>) {
+        let mut state = self.state.lock().unwrap();
+        state.follower.follow(&event);
+        if let GameEvent::MoveHappened(_) = event {
+            state.turn_since = None;
+        }
+        if !state.follower.to_move() {
+            state.turn_since = None;
+        } else if state.turn_since.is_none() {
+            state.turn_since = Some(Instant::now());
+        }
+        if let Some(link) = &state.link {
+            let buf = bincode::serialize(&event).unwrap();
+            link.send(Ok(Message::binary(buf))).ok();
+        }
+    }
+
+    fn ping(&self) {
+        let state = self.state.lock().unwrap();
+        if let Some(link) = &state.link {
+            link.send(Ok(Message::ping(Vec::new()))).ok();
+        }
+    }
+
+    // Why the player should forfeit, if they should: gone past the grace period, or out of time.
+    // A connection that stops answering pings counts as gone.
+    fn failed(&self) -> Option<&'static str> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(interval) = self.timeouts.heartbeat {
+            if state.link.is_some() && state.heard.elapsed() > interval * MISSED_PINGS {
+                state.link = None;
+                state.dropped = Some(Instant::now());
+                // The silent socket's reader may still end later; it should not reset the grace period
+                state.connection += 1;
+            }
+        }
+        match (state.dropped, state.turn_since, self.timeouts.move_time) {
+            (Some(since), _, _) if since.elapsed() > self.timeouts.grace => Some("connection lost"),
+            (_, Some(since), Some(limit)) if since.elapsed() > limit => Some("out of time"),
+            _ => None,
+        }
+    }
+
+    fn restart_clock(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.turn_since = state.turn_since.map(|_| Instant::now());
+    }
 }
 
-impl<G: Game> WSHost for AgentCore<G> {
-    fn host(self, socket: WebSocket, games: Games, lobbies: Lobbies, name: String) {
+trait WSSeat: Send + Sync {
+    fn attach(self: Arc<Self>, socket: WebSocket, games: Games, lobbies: Lobbies);
+    fn game(&self) -> &str;
+    // Ends the game for this seat because the player in seat `by` forfeited
+    fn forfeit(&self, by: PlayerID, winner: Option<PlayerID>);
+}
+
+impl<G: Game> WSSeat for WebSeat<G>
+where
+    G::Move: Copy,
+{
+    // Gives the seat a new connection: the session token goes first, as text, then the game so far
+    fn attach(self: Arc<Self>, socket: WebSocket, games: Games, lobbies: Lobbies) {
         let (wstx, mut wsrx) = socket.split();
 
         let (tx, rx) = mpsc::unbounded_channel();
-        //let quit_tx = tx.clone();
         tokio::spawn(rx.forward(wstx));
-        let mc = self.move_channel;
-        let ec = self.event_channel;
+        tx.send(Ok(Message::text(self.token.clone()))).ok();
+        let connection = {
+            let mut state = self.state.lock().unwrap();
+            let side = state.follower.seat();
+            for event in state.follower.catch_up(side) {
+                let buf = bincode::serialize(&event).unwrap();
+                tx.send(Ok(Message::binary(buf))).ok();
+            }
+            state.link = Some(tx);
+            state.dropped = None;
+            state.heard = Instant::now();
+            state.connection += 1;
+            state.connection
+        };
+
         tokio::spawn(async move {
             while let Some(result) = wsrx.next().await {
+                if result.is_ok() {
+                    self.state.lock().unwrap().heard = Instant::now();
+                }
                 match result {
+                    Ok(msg) if msg.is_close() => break,
+                    Ok(msg) if msg.is_ping() || msg.is_pong() => {}
                     Ok(msg) => {
                         let buf = msg.as_bytes();
                         if let Ok(qmv) = bincode::deserialize::<G::Move>(buf) {
-                            mc.send(qmv).unwrap();
-                            if let Some(t) = games.write().await.get_mut(&name) {
-                                t().unwrap();
+                            self.moves.send(qmv).unwrap();
+                            let step = games
+                                .read()
+                                .await
+                                .get(&self.name)
+                                .map(|table| table.step.clone());
+                            if let Some(step) = step {
+                                let stepped = tokio::task::spawn_blocking(move || {
+                                    (step.lock().unwrap())().map_err(|e| e.to_string())
+                                })
+                                .await;
+                                if let Ok(Err(e)) = stepped {
+                                    eprintln!("Game {} stopped: {}", self.name, e);
+                                }
                             }
                         } else {
                             //let buf = bincode::serialize(&GameEvent::<G>::OpponentQuit).unwrap();
                             eprintln!("Someone quit!");
-                            games.write().await.remove(&name);
-                            lobbies.write().await.remove(&name);
-                            //quit_tx.send(Ok(Message::binary(buf))).unwrap();
+                            games.write().await.remove(&self.name);
+                            lobbies.write().await.remove(&self.name);
                         }
                     }
                     Err(_) => break,
                 }
             }
-        });
 
-        tokio::spawn(async move {
-            loop {
-                if let Ok(msg) = ec.try_recv() {
-                    let buf = bincode::serialize(&msg).unwrap();
-                    tx.send(Ok(Message::binary(buf))).unwrap();
-                }
-                tokio::task::yield_now().await;
+            // Hold the seat for a while in case the player comes back
+            let mut state = self.state.lock().unwrap();
+            if state.connection == connection {
+                state.link = None;
+                state.dropped = Some(Instant::now());
             }
         });
     }
+
+    fn game(&self) -> &str {
+        &self.name
+    }
+
+    fn forfeit(&self, by: PlayerID, winner: Option<PlayerID>) {
+        if by != self.state.lock().unwrap().follower.seat() {
+            self.forward(GameEvent::OpponentQuit);
+        }
+        self.forward(GameEvent::GameEnd(winner));
+    }
 }
 
 #[derive(Debug)]
 struct UnimplementedGameType;
 impl warp::reject::Reject for UnimplementedGameType {}
+
+#[derive(Debug)]
+struct UnknownBotConfig;
+impl warp::reject::Reject for UnknownBotConfig {}